@@ -0,0 +1,210 @@
+//! Runs a command, retrying it with backoff until it succeeds:
+//!
+//! ```sh
+//! retry --max-retries 5 --base 200ms --jitter full -- curl -f https://example.com
+//! ```
+//!
+//! The command's standard streams are passed straight through,
+//! and `retry` exits with the status of the last attempt.
+
+use std::fmt;
+use std::io;
+use std::process::{exit, Command, ExitStatus};
+use std::time::Duration;
+
+use clap::Parser;
+use mysteriouspants_retry::{
+    CancelError, CancellationToken, ExponentialBackoff, Jitter
+};
+
+/// Runs a command, retrying it with exponential backoff until
+/// it succeeds.
+#[derive(Debug, Parser)]
+#[command(name = "retry", version)]
+struct Args {
+
+    /// The maximum number of attempts to make.
+    #[arg(long, default_value_t = 5)]
+    max_retries: u8,
+
+    /// The backoff time before the first retry.
+    #[arg(long, default_value = "1s", value_parser = humantime::parse_duration)]
+    base: Duration,
+
+    /// The exponent to raise the attempt number to.
+    #[arg(long, default_value_t = 2.0)]
+    exponent: f32,
+
+    /// Caps the time waited before any single retry.
+    #[arg(long, value_parser = humantime::parse_duration)]
+    max_delay: Option<Duration>,
+
+    /// Bounds the total time spent retrying.
+    #[arg(long, value_parser = humantime::parse_duration)]
+    max_elapsed: Option<Duration>,
+
+    /// The randomness to mix into each backoff time: none, full,
+    /// equal or decorrelated.
+    #[arg(long, default_value = "none", value_parser = parse_jitter)]
+    jitter: Jitter,
+
+    /// Retries only these exit codes, rather than every failure.
+    #[arg(long, value_delimiter = ',')]
+    retry_on: Vec<i32>,
+
+    /// Retries only when killed by these signals, rather than
+    /// every failure.
+    #[arg(long, value_delimiter = ',')]
+    retry_on_signal: Vec<i32>,
+
+    /// Does not report failed attempts on standard error.
+    #[arg(short, long)]
+    quiet: bool,
+
+    /// The command to run, and its arguments.
+    #[arg(required = true, last = true)]
+    command: Vec<String>
+}
+
+/// Why an attempt at running the command failed.
+#[derive(Debug)]
+enum Failure {
+
+    /// The command ran, and did not succeed.
+    Status(ExitStatus),
+
+    /// The command could not be started.
+    Spawn(io::Error)
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Failure::Status(status) => status.fmt(f),
+            Failure::Spawn(error) => error.fmt(f)
+        }
+    }
+}
+
+fn main() {
+    let args = Args::parse();
+
+    let token = CancellationToken::new();
+    let interrupted = token.clone();
+    ctrlc::set_handler(move || interrupted.cancel())
+        .expect("could not install the Ctrl-C handler");
+
+    let backoff = match backoff(&args) {
+        Ok(backoff) => backoff,
+        Err(error) => {
+            eprintln!("retry: {}", error);
+            exit(2);
+        }
+    };
+
+    let (program, program_args) = args.command.split_first()
+        .expect("clap requires a command");
+    let result = backoff.retry_cancellable(&token, || {
+        match Command::new(program).args(program_args).status() {
+            Ok(status) if status.success() => Ok(status),
+            Ok(status) => Err(Failure::Status(status)),
+            Err(error) => Err(Failure::Spawn(error))
+        }
+    });
+
+    exit(match result {
+        Ok(_) => 0,
+        Err(CancelError::Cancelled) => 130,
+        Err(CancelError::Inner(Failure::Status(status))) => exit_code(status),
+        Err(CancelError::Inner(Failure::Spawn(error))) => {
+            eprintln!("retry: could not run {}: {}", program, error);
+            match error.kind() {
+                io::ErrorKind::NotFound => 127,
+                _ => 126
+            }
+        }
+    });
+}
+
+/// Builds the backoff described by `args`.
+fn backoff(
+    args: &Args
+) -> Result<ExponentialBackoff<ExitStatus, Failure>, mysteriouspants_retry::ConfigError> {
+    let retry_on = args.retry_on.clone();
+    let retry_on_signal = args.retry_on_signal.clone();
+    let quiet = args.quiet;
+
+    let mut builder = ExponentialBackoff::builder()
+        .max_retries(args.max_retries)
+        .base(args.base)
+        .exponent(args.exponent)
+        .jitter(args.jitter)
+        .should_retry(move |result: &Result<ExitStatus, Failure>| match result {
+            Err(Failure::Status(status)) => {
+                should_retry(*status, &retry_on, &retry_on_signal)
+            },
+            _ => false
+        })
+        .on_retry(move |attempt, result, backoff_time| {
+            if let (false, Err(failure)) = (quiet, result) {
+                eprintln!(
+                    "retry: attempt {} failed with {}, retrying in {}",
+                    attempt, failure, humantime::format_duration(backoff_time)
+                );
+            }
+        });
+
+    if let Some(max_delay) = args.max_delay {
+        builder = builder.max_delay(max_delay);
+    }
+    if let Some(max_elapsed) = args.max_elapsed {
+        builder = builder.max_elapsed(max_elapsed);
+    }
+
+    builder.build()
+}
+
+/// Whether a command which finished with `status` should be
+/// run again. With neither list given, every failure is.
+fn should_retry(status: ExitStatus, retry_on: &[i32], retry_on_signal: &[i32]) -> bool {
+    if retry_on.is_empty() && retry_on_signal.is_empty() {
+        return true;
+    }
+
+    match (status.code(), signal(status)) {
+        (Some(code), _) => retry_on.contains(&code),
+        (None, Some(signal)) => retry_on_signal.contains(&signal),
+        (None, None) => false
+    }
+}
+
+/// The exit code to pass on for a command which finished with
+/// `status`, following the shell's convention of 128 plus the
+/// signal number for commands killed by a signal.
+fn exit_code(status: ExitStatus) -> i32 {
+    match (status.code(), signal(status)) {
+        (Some(code), _) => code,
+        (None, Some(signal)) => 128 + signal,
+        (None, None) => 1
+    }
+}
+
+#[cfg(unix)]
+fn signal(status: ExitStatus) -> Option<i32> {
+    std::os::unix::process::ExitStatusExt::signal(&status)
+}
+
+#[cfg(not(unix))]
+fn signal(_status: ExitStatus) -> Option<i32> {
+    None
+}
+
+fn parse_jitter(jitter: &str) -> Result<Jitter, String> {
+    match jitter {
+        "none" => Ok(Jitter::None),
+        "full" => Ok(Jitter::Full),
+        "equal" => Ok(Jitter::Equal),
+        "decorrelated" => Ok(Jitter::Decorrelated),
+        _ => Err(String::from("expected none, full, equal or decorrelated"))
+    }
+}
@@ -0,0 +1,87 @@
+#![cfg(unix)]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// Runs `retry` with `args`, followed by a shell running
+/// `script`.
+fn retry(args: &[&str], script: &str) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_retry"))
+        .args(args)
+        .args(["--base", "1ms", "--", "sh", "-c", script])
+        .output()
+        .unwrap()
+}
+
+/// A fresh file for a script to count its runs in.
+fn counter(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "retry-cli-{}-{}", name, std::process::id()
+    ));
+    let _ = fs::remove_file(&path);
+    path
+}
+
+fn runs(counter: &Path) -> usize {
+    fs::read_to_string(counter).unwrap().lines().count()
+}
+
+/// A script which records each run in `counter`, failing with
+/// `code` until its `succeed_on`th run.
+fn flaky(counter: &Path, succeed_on: usize, code: i32) -> String {
+    format!(
+        "echo run >> {0}; [ $(wc -l < {0}) -ge {1} ] || exit {2}",
+        counter.display(), succeed_on, code
+    )
+}
+
+#[test]
+fn retries_until_success() {
+    let counter = counter("success");
+    let output = retry(&["--max-retries", "5"], &flaky(&counter, 3, 1));
+
+    assert!(output.status.success());
+    assert_eq!(runs(&counter), 3);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr).matches("retrying in").count(),
+        2
+    );
+}
+
+#[test]
+fn exits_with_last_status() {
+    let counter = counter("exhausted");
+    let output = retry(&["--max-retries", "3", "--quiet"], &flaky(&counter, 10, 7));
+
+    assert_eq!(output.status.code(), Some(7));
+    assert_eq!(runs(&counter), 3);
+    assert!(output.stderr.is_empty());
+}
+
+#[test]
+fn retries_only_chosen_exit_codes() {
+    let counter = counter("codes");
+    let output = retry(&["--retry-on", "75,76"], &flaky(&counter, 10, 3));
+
+    assert_eq!(output.status.code(), Some(3));
+    assert_eq!(runs(&counter), 1);
+}
+
+#[test]
+fn forwards_stdout() {
+    let output = retry(&[], "echo hello");
+
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"hello\n");
+}
+
+#[test]
+fn reports_missing_commands() {
+    let output = Command::new(env!("CARGO_BIN_EXE_retry"))
+        .args(["--", "/nonexistent/command"])
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(127));
+}
@@ -0,0 +1,477 @@
+//! Procedural macros for
+//! [mysteriouspants-retry](https://docs.rs/mysteriouspants-retry),
+//! which re-exports them behind its `macros` feature.
+
+use std::time::Duration;
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote, quote_spanned};
+use syn::spanned::Spanned;
+use syn::{
+    parse_macro_input, Attribute, Data, DeriveInput, Expr, Fields, FnArg,
+    Ident, ItemFn, LitFloat, LitInt, LitStr, Member, Pat, ReturnType
+};
+
+/// Derives `Retryable` from `#[retry(...)]` attributes, which
+/// classify each variant of an enum (or a whole struct):
+///
+/// * `#[retry(transient)]` retries it on the backoff schedule.
+/// * `#[retry(permanent)]` stops retrying.
+/// * `#[retry(after = "field")]` retries it after the wait held
+///   in `field`, which may be a `Duration` or an
+///   `Option<Duration>`; tuple fields are named by index, as in
+///   `after = "0"`. When the field is `None`, the variant is
+///   retried on the backoff schedule.
+///
+/// An attribute on the enum itself sets the classification of
+/// any variant without one. Otherwise every variant must be
+/// classified, so that adding a variant without deciding
+/// whether it is worth retrying is a compile error.
+#[proc_macro_derive(Retryable, attributes(retry))]
+pub fn derive_retryable(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match expand_retryable(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(error) => error.to_compile_error().into()
+    }
+}
+
+/// How a variant ought to be retried.
+enum Classification {
+    Transient,
+    Permanent,
+    After(LitStr)
+}
+
+fn expand_retryable(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let default = classification(&input.attrs)?;
+    let arms = match &input.data {
+        Data::Enum(data) => data.variants.iter().map(|variant| {
+            let ident = &variant.ident;
+            let class = match classification(&variant.attrs)? {
+                Some(class) => class,
+                None => match &default {
+                    Some(Classification::After(_)) | None => {
+                        return Err(syn::Error::new(
+                            variant.span(),
+                            "missing #[retry(transient)], #[retry(permanent)] \
+                            or #[retry(after = \"field\")]"
+                        ));
+                    },
+                    Some(Classification::Transient) => Classification::Transient,
+                    Some(Classification::Permanent) => Classification::Permanent
+                }
+            };
+            arm(quote!(Self::#ident), &variant.fields, &class)
+        }).collect::<syn::Result<Vec<_>>>()?,
+        Data::Struct(data) => {
+            let class = default.as_ref().ok_or_else(|| syn::Error::new(
+                input.ident.span(),
+                "missing #[retry(transient)], #[retry(permanent)] \
+                or #[retry(after = \"field\")]"
+            ))?;
+            vec![arm(quote!(Self), &data.fields, class)?]
+        },
+        Data::Union(data) => {
+            return Err(syn::Error::new(
+                data.union_token.span(),
+                "Retryable cannot be derived for unions"
+            ));
+        }
+    };
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let transient = arms.iter().map(|arm| &arm.transient);
+    let decisions = arms.iter().map(|arm| &arm.decision);
+
+    Ok(quote! {
+        impl #impl_generics ::mysteriouspants_retry::Retryable
+            for #name #ty_generics #where_clause {
+
+            fn is_transient(&self) -> bool {
+                match self {
+                    #(#transient,)*
+                }
+            }
+
+            fn retry_decision(&self) -> ::mysteriouspants_retry::Decision {
+                match self {
+                    #(#decisions,)*
+                }
+            }
+        }
+    })
+}
+
+/// The match arms classifying one variant.
+struct Arm {
+    transient: TokenStream2,
+    decision: TokenStream2
+}
+
+fn arm(
+    path: TokenStream2,
+    fields: &Fields,
+    class: &Classification
+) -> syn::Result<Arm> {
+    let decision = quote!(::mysteriouspants_retry::Decision);
+
+    Ok(match class {
+        Classification::Transient => Arm {
+            transient: quote!(#path { .. } => true),
+            decision: quote!(#path { .. } => #decision::Retry)
+        },
+        Classification::Permanent => Arm {
+            transient: quote!(#path { .. } => false),
+            decision: quote!(#path { .. } => #decision::Stop)
+        },
+        Classification::After(field) => {
+            let member = member(fields, field)?;
+            let binding = format_ident!("__retry_after");
+            let value = quote_spanned! { field.span() =>
+                ::mysteriouspants_retry::__private::RetryAfterValue::retry_after(
+                    #binding
+                )
+            };
+
+            Arm {
+                transient: quote!(#path { .. } => true),
+                decision: quote! {
+                    #path { #member: #binding, .. } => match #value {
+                        ::std::option::Option::Some(after) => #decision::RetryAfter(after),
+                        ::std::option::Option::None => #decision::Retry
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// Finds the field named by `after = "..."`.
+fn member(fields: &Fields, field: &LitStr) -> syn::Result<Member> {
+    let name = field.value();
+
+    let found = fields.iter().enumerate().find_map(|(index, candidate)| {
+        match &candidate.ident {
+            Some(ident) if *ident == name => Some(Member::Named(ident.clone())),
+            None if index.to_string() == name => Some(Member::Unnamed(index.into())),
+            _ => None
+        }
+    });
+
+    found.ok_or_else(|| syn::Error::new(
+        field.span(), format!("no field named `{}`", name)
+    ))
+}
+
+/// Reads the `#[retry(...)]` attribute among `attrs`, if any.
+fn classification(attrs: &[Attribute]) -> syn::Result<Option<Classification>> {
+    let mut class = None;
+
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("retry")) {
+        attr.parse_nested_meta(|meta| {
+            let parsed = if meta.path.is_ident("transient") {
+                Classification::Transient
+            } else if meta.path.is_ident("permanent") {
+                Classification::Permanent
+            } else if meta.path.is_ident("after") {
+                Classification::After(meta.value()?.parse()?)
+            } else {
+                return Err(meta.error(
+                    "expected `transient`, `permanent` or `after = \"field\"`"
+                ));
+            };
+
+            if class.replace(parsed).is_some() {
+                return Err(meta.error("conflicting retry classifications"));
+            }
+            Ok(())
+        })?;
+    }
+
+    Ok(class)
+}
+
+/// Runs the body of a function under the retry loop of an
+/// `ExponentialBackoff`, configured by the attribute's
+/// arguments:
+///
+/// * `max_retries = 5` sets the maximum number of attempts.
+/// * `base = "100ms"` sets the backoff time before the first
+///   retry, written the human-readable way.
+/// * `exponent = 2.0` sets the exponent the attempt number is
+///   raised to.
+/// * `max_delay = "10s"` caps the time waited before any single
+///   retry.
+/// * `when = is_transient` retries only errors for which the
+///   given function of `&E` returns `true` or
+///   `Decision::Retry`, rather than every error.
+/// * `sleeper = TokioSleeper` waits between attempts of an
+///   `async fn`, and is required for them.
+///
+/// The function must return a `Result`. Each attempt runs the
+/// whole body again, borrowing the arguments, so the body sees
+/// them just as it would without the attribute, whether or not
+/// the function is `async`. A body which moves an argument
+/// must clone it instead, since later attempts need it too. A
+/// binding declared `mut` is the exception: each attempt starts
+/// from its own clone of the value passed in, so it must be
+/// `Clone`.
+///
+/// The settings are checked when the function is compiled, so
+/// one which would make `ExponentialBackoffBuilder::build` fail
+/// is a compile error rather than a panic.
+///
+/// The attribute shares its name with the helper attribute of
+/// `#[derive(Retryable)]`, so the two cannot both be imported
+/// by name into the same module; one can be written out in full
+/// as `#[mysteriouspants_retry::retry(...)]` instead.
+#[proc_macro_attribute]
+pub fn retry(args: TokenStream, item: TokenStream) -> TokenStream {
+    let mut settings = Settings::default();
+    let parser = syn::meta::parser(|meta| settings.parse(meta));
+    parse_macro_input!(args with parser);
+    let function = parse_macro_input!(item as ItemFn);
+
+    match expand_retry(&settings, function) {
+        Ok(tokens) => tokens.into(),
+        Err(error) => error.to_compile_error().into()
+    }
+}
+
+/// The arguments of a `#[retry(...)]` attribute on a function.
+#[derive(Default)]
+struct Settings {
+    max_retries: Option<LitInt>,
+    base: Option<LitStr>,
+    exponent: Option<LitFloat>,
+    max_delay: Option<LitStr>,
+    when: Option<Expr>,
+    sleeper: Option<Expr>
+}
+
+impl Settings {
+    fn parse(&mut self, meta: syn::meta::ParseNestedMeta) -> syn::Result<()> {
+        if meta.path.is_ident("max_retries") {
+            let max_retries: LitInt = meta.value()?.parse()?;
+            if max_retries.base10_parse::<u8>()? == 0 {
+                return Err(syn::Error::new(
+                    max_retries.span(), "max_retries must be at least one"
+                ));
+            }
+            self.max_retries = Some(max_retries);
+        } else if meta.path.is_ident("base") {
+            self.base = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("exponent") {
+            self.exponent = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("max_delay") {
+            self.max_delay = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("when") {
+            self.when = Some(meta.value()?.parse()?);
+        } else if meta.path.is_ident("sleeper") {
+            self.sleeper = Some(meta.value()?.parse()?);
+        } else {
+            return Err(meta.error(
+                "expected `max_retries`, `base`, `exponent`, `max_delay`, \
+                `when` or `sleeper`"
+            ));
+        }
+        Ok(())
+    }
+
+    /// Checks that the backoff time before every retry can be
+    /// represented, as `ExponentialBackoffBuilder::build` would
+    /// at run time, so that the expansion can build its backoff
+    /// without checking it again on every call.
+    fn check_delays(&self) -> syn::Result<()> {
+        let max_retries = match &self.max_retries {
+            Some(max_retries) => max_retries.base10_parse::<u8>()?,
+            None => 7
+        };
+        let base = match &self.base {
+            Some(base) => duration(base)?,
+            None => Duration::from_secs(1)
+        };
+        let exponent = match &self.exponent {
+            Some(exponent) => {
+                let value = exponent.base10_parse::<f32>()?;
+                if !value.is_finite() {
+                    return Err(syn::Error::new(
+                        exponent.span(), "exponent must be finite"
+                    ));
+                }
+                value
+            },
+            None => 0.5
+        };
+
+        for attempt in 1..u32::from(max_retries) {
+            // the same arithmetic as `exponential_delay`, with no
+            // constant to add
+            let factor = f64::from(attempt).powf(f64::from(exponent));
+            let delay = if factor.fract() == 0.0 && factor <= f64::from(u32::MAX) {
+                base.checked_mul(factor as u32)
+            } else {
+                Duration::try_from_secs_f64(base.as_secs_f64() * factor).ok()
+            };
+
+            if delay.is_none() {
+                return Err(syn::Error::new(
+                    Span::call_site(),
+                    format!("backoff time for attempt {} is too large", attempt)
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn expand_retry(settings: &Settings, mut function: ItemFn) -> syn::Result<TokenStream2> {
+    let private = quote!(::mysteriouspants_retry::__private);
+    let body = &function.block;
+    settings.check_delays()?;
+
+    let mut setters = Vec::new();
+    if let Some(max_retries) = &settings.max_retries {
+        setters.push(quote!(.max_retries(#max_retries)));
+    }
+    if let Some(base) = &settings.base {
+        let base = duration_expr(duration(base)?);
+        setters.push(quote!(.base(#base)));
+    }
+    if let Some(exponent) = &settings.exponent {
+        setters.push(quote!(.exponent(#exponent)));
+    }
+    if let Some(max_delay) = &settings.max_delay {
+        let max_delay = duration_expr(duration(max_delay)?);
+        setters.push(quote!(.max_delay(#max_delay)));
+    }
+    if let Some(when) = &settings.when {
+        setters.push(quote! {
+            .should_retry(|__result: &::std::result::Result<_, _>| match __result {
+                ::std::result::Result::Ok(_) => ::mysteriouspants_retry::Decision::Stop,
+                ::std::result::Result::Err(__error) => {
+                    ::std::convert::Into::into((#when)(__error))
+                }
+            })
+        });
+    }
+
+    let output = match &function.sig.output {
+        ReturnType::Type(_, output) => output,
+        ReturnType::Default => return Err(syn::Error::new(
+            function.sig.span(), "#[retry] needs a function returning a `Result`"
+        ))
+    };
+
+    // attempts borrow the arguments, except for `mut` bindings,
+    // which each attempt gives a clone of its own to change
+    let mut bindings = Vec::new();
+    for input in function.sig.inputs.iter_mut() {
+        if let FnArg::Typed(typed) = input {
+            take_mut_bindings(&mut typed.pat, &mut bindings);
+        }
+    }
+    let clones = bindings.iter().map(|binding| {
+        quote!(let mut #binding = ::std::clone::Clone::clone(&#binding);)
+    });
+
+    let backoff = quote! {
+        let __backoff = #private::prechecked(#private::builder_for::<#output>()
+            #(#setters)*);
+        let mut __retrying = #private::retrying(&__backoff);
+    };
+
+    let block = match &function.sig.asyncness {
+        None => quote! {{
+            #backoff
+            loop {
+                let __result = __retrying.attempt(|| {
+                    #(#clones)*
+                    #body
+                });
+                match __retrying.next(__result) {
+                    ::std::ops::ControlFlow::Continue(__wait) => __retrying.sleep(__wait),
+                    ::std::ops::ControlFlow::Break(__result) => return __result
+                }
+            }
+        }},
+        Some(asyncness) => {
+            let sleeper = settings.sleeper.as_ref().ok_or_else(|| syn::Error::new(
+                asyncness.span(),
+                "#[retry] on an async fn needs a `sleeper = ...` to wait with"
+            ))?;
+
+            quote! {{
+                let __sleeper = #sleeper;
+                #backoff
+                loop {
+                    let __result = __retrying.instrument(async {
+                        #(#clones)*
+                        #body
+                    }).await;
+                    match __retrying.next(__result) {
+                        ::std::ops::ControlFlow::Continue(__wait) => {
+                            ::mysteriouspants_retry::AsyncSleeper::sleep(&__sleeper, __wait)
+                                .await;
+                        },
+                        ::std::ops::ControlFlow::Break(__result) => return __result
+                    }
+                }
+            }}
+        }
+    };
+
+    function.block = syn::parse2(block)?;
+    Ok(quote!(#function))
+}
+
+/// Takes `mut` off the by-value bindings in an argument
+/// pattern, collecting them so each attempt can rebind them.
+fn take_mut_bindings(pat: &mut Pat, bindings: &mut Vec<Ident>) {
+    match pat {
+        Pat::Ident(pat) => {
+            if pat.by_ref.is_none() && pat.mutability.take().is_some() {
+                bindings.push(pat.ident.clone());
+            }
+            if let Some((_, subpat)) = &mut pat.subpat {
+                take_mut_bindings(subpat, bindings);
+            }
+        },
+        Pat::Paren(pat) => take_mut_bindings(&mut pat.pat, bindings),
+        Pat::Reference(pat) => take_mut_bindings(&mut pat.pat, bindings),
+        Pat::Slice(pat) => for elem in pat.elems.iter_mut() {
+            take_mut_bindings(elem, bindings);
+        },
+        Pat::Struct(pat) => for field in pat.fields.iter_mut() {
+            take_mut_bindings(&mut field.pat, bindings);
+        },
+        Pat::Tuple(pat) => for elem in pat.elems.iter_mut() {
+            take_mut_bindings(elem, bindings);
+        },
+        Pat::TupleStruct(pat) => for elem in pat.elems.iter_mut() {
+            take_mut_bindings(elem, bindings);
+        },
+        Pat::Type(pat) => take_mut_bindings(&mut pat.pat, bindings),
+        _ => {}
+    }
+}
+
+/// Parses a human-readable duration, such as `"1m 30s"`.
+fn duration(literal: &LitStr) -> syn::Result<Duration> {
+    humantime::parse_duration(&literal.value()).map_err(|error| {
+        syn::Error::new(literal.span(), format!("invalid duration: {}", error))
+    })
+}
+
+/// The expression building `duration`.
+fn duration_expr(duration: Duration) -> TokenStream2 {
+    let secs = duration.as_secs();
+    let nanos = duration.subsec_nanos();
+
+    quote!(::std::time::Duration::new(#secs, #nanos))
+}
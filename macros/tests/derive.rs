@@ -0,0 +1,65 @@
+use mysteriouspants_retry::{Decision, Retryable};
+use std::time::Duration;
+
+#[allow(dead_code)]
+#[derive(Retryable)]
+enum ServiceError {
+    #[retry(transient)]
+    Unavailable,
+    #[retry(permanent)]
+    NotFound { path: String },
+    #[retry(after = "retry_after")]
+    Throttled { retry_after: Duration },
+    #[retry(after = "1")]
+    MaybeThrottled(u16, Option<Duration>)
+}
+
+#[allow(dead_code)]
+#[derive(Retryable)]
+#[retry(permanent)]
+enum ParseError {
+    #[retry(transient)]
+    Truncated,
+    BadMagic,
+    BadVersion(u8)
+}
+
+#[derive(Retryable)]
+#[retry(transient)]
+struct Timeout;
+
+#[test]
+fn classifies_variants() {
+    let throttled = ServiceError::Throttled {
+        retry_after: Duration::from_secs(30)
+    };
+    let not_found = ServiceError::NotFound { path: "/".to_string() };
+
+    assert!(ServiceError::Unavailable.is_transient());
+    assert!(!not_found.is_transient());
+    assert!(throttled.is_transient());
+
+    assert_eq!(ServiceError::Unavailable.retry_decision(), Decision::Retry);
+    assert_eq!(not_found.retry_decision(), Decision::Stop);
+    assert_eq!(
+        throttled.retry_decision(),
+        Decision::RetryAfter(Duration::from_secs(30))
+    );
+    assert_eq!(
+        ServiceError::MaybeThrottled(429, Some(Duration::from_secs(2)))
+            .retry_decision(),
+        Decision::RetryAfter(Duration::from_secs(2))
+    );
+    assert_eq!(
+        ServiceError::MaybeThrottled(503, None).retry_decision(),
+        Decision::Retry
+    );
+}
+
+#[test]
+fn falls_back_to_container_classification() {
+    assert!(ParseError::Truncated.is_transient());
+    assert!(!ParseError::BadMagic.is_transient());
+    assert!(!ParseError::BadVersion(2).is_transient());
+    assert!(Timeout.is_transient());
+}
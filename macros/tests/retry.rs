@@ -0,0 +1,193 @@
+use mysteriouspants_retry::{retry, MockClock, Retryable};
+use std::cell::Cell;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum FetchError {
+    Unavailable,
+    NotFound
+}
+
+impl Retryable for FetchError {
+    fn is_transient(&self) -> bool {
+        *self == FetchError::Unavailable
+    }
+}
+
+/// Fails with `error` until the `succeed_on`th attempt.
+fn attempt(attempts: &Cell<u32>, succeed_on: u32, error: FetchError) -> Result<u32, FetchError> {
+    attempts.set(attempts.get() + 1);
+    if attempts.get() < succeed_on {
+        Err(error)
+    } else {
+        Ok(attempts.get())
+    }
+}
+
+#[retry(max_retries = 5, base = "1ms")]
+fn fetch(attempts: &Cell<u32>, succeed_on: u32) -> Result<u32, FetchError> {
+    let value = attempt(attempts, succeed_on, FetchError::Unavailable)?;
+    Ok(value * 10)
+}
+
+#[retry(max_retries = 3, base = "1ms", exponent = 1.0, max_delay = "2ms")]
+fn fetch_with_early_return(attempts: &Cell<u32>) -> Result<u32, FetchError> {
+    if attempts.get() == 1 {
+        attempts.set(2);
+        return Ok(0);
+    }
+    attempt(attempts, u32::MAX, FetchError::Unavailable)
+}
+
+#[retry(max_retries = 5, base = "1ms", when = Retryable::is_transient)]
+fn fetch_when(attempts: &Cell<u32>, error: FetchError) -> Result<u32, FetchError> {
+    attempt(attempts, u32::MAX, error)
+}
+
+#[retry(max_retries = 3, base = "1ms")]
+fn fetch_counting_down(attempts: &Cell<u32>, mut remaining: u32) -> Result<u32, FetchError> {
+    // every attempt starts from the value passed in
+    remaining -= 1;
+    attempt(attempts, 3, FetchError::Unavailable)?;
+    Ok(remaining)
+}
+
+#[retry(max_retries = 3, base = "1ms")]
+fn fetch_pair(attempts: &Cell<u32>, (left, right): (u32, u32)) -> Result<u32, FetchError> {
+    attempt(attempts, 2, FetchError::Unavailable).map(|_| left + right)
+}
+
+#[retry(max_retries = 3, base = "1s", sleeper = MockClock::new())]
+async fn fetch_async(attempts: &Cell<u32>, mut n: u32, names: Vec<&str>) -> Result<usize, FetchError> {
+    n += 1;
+    attempt(attempts, 2, FetchError::Unavailable)?;
+    Ok(n as usize + names.len())
+}
+
+#[retry(max_retries = 3, base = "1ms")]
+fn fill(attempts: &Cell<u32>, buf: &mut Vec<u32>) -> Result<usize, FetchError> {
+    // changes through a `&mut` argument carry over between attempts
+    buf.push(attempts.get());
+    attempt(attempts, 3, FetchError::Unavailable)?;
+    Ok(buf.len())
+}
+
+/// A value which cannot be cloned.
+struct Token(u32);
+
+#[retry(max_retries = 3, base = "1ms")]
+fn redeem(attempts: &Cell<u32>, token: Token) -> Result<u32, FetchError> {
+    attempt(attempts, 2, FetchError::Unavailable)?;
+    Ok(token.0)
+}
+
+#[retry(max_retries = 3, base = "1s", sleeper = MockClock::new())]
+async fn fill_async(attempts: &Cell<u32>, buf: &mut Vec<u32>, token: Token) -> Result<u32, FetchError> {
+    buf.push(token.0);
+    attempt(attempts, 2, FetchError::Unavailable)
+}
+
+struct Client {
+    attempts: Cell<u32>
+}
+
+impl Client {
+    #[retry(max_retries = 4, base = "1ms")]
+    fn get(&self) -> Result<u32, FetchError> {
+        attempt(&self.attempts, 2, FetchError::Unavailable)
+    }
+
+    #[retry(max_retries = 4, base = "1s", sleeper = MockClock::new())]
+    async fn get_async(&self, succeed_on: u32) -> Result<u32, FetchError> {
+        attempt(&self.attempts, succeed_on, FetchError::Unavailable)
+    }
+}
+
+#[test]
+fn retries_until_success() {
+    let attempts = Cell::new(0);
+
+    assert_eq!(fetch(&attempts, 3), Ok(30));
+    assert_eq!(attempts.get(), 3);
+}
+
+#[test]
+fn gives_up_after_max_retries() {
+    let attempts = Cell::new(0);
+
+    assert_eq!(fetch(&attempts, 10), Err(FetchError::Unavailable));
+    assert_eq!(attempts.get(), 5);
+}
+
+#[test]
+fn returns_from_the_attempt() {
+    let attempts = Cell::new(0);
+
+    assert_eq!(fetch_with_early_return(&attempts), Ok(0));
+    assert_eq!(attempts.get(), 2);
+}
+
+#[test]
+fn retries_only_when_asked() {
+    let attempts = Cell::new(0);
+    assert_eq!(fetch_when(&attempts, FetchError::NotFound), Err(FetchError::NotFound));
+    assert_eq!(attempts.get(), 1);
+
+    let attempts = Cell::new(0);
+    assert_eq!(fetch_when(&attempts, FetchError::Unavailable), Err(FetchError::Unavailable));
+    assert_eq!(attempts.get(), 5);
+}
+
+#[test]
+fn gives_every_attempt_its_own_arguments() {
+    let attempts = Cell::new(0);
+    assert_eq!(fetch_counting_down(&attempts, 5), Ok(4));
+    assert_eq!(attempts.get(), 3);
+
+    let attempts = Cell::new(0);
+    assert_eq!(fetch_pair(&attempts, (1, 2)), Ok(3));
+    assert_eq!(attempts.get(), 2);
+}
+
+#[test]
+fn borrows_arguments_which_cannot_be_cloned() {
+    let attempts = Cell::new(0);
+    let mut buf = Vec::new();
+    assert_eq!(fill(&attempts, &mut buf), Ok(3));
+    assert_eq!(buf, vec![0, 1, 2]);
+
+    let attempts = Cell::new(0);
+    assert_eq!(redeem(&attempts, Token(7)), Ok(7));
+    assert_eq!(attempts.get(), 2);
+}
+
+#[test]
+fn retries_methods() {
+    let client = Client { attempts: Cell::new(0) };
+
+    assert_eq!(client.get(), Ok(2));
+}
+
+#[tokio::test]
+async fn retries_async_functions() {
+    let client = Client { attempts: Cell::new(0) };
+
+    assert_eq!(client.get_async(3).await, Ok(3));
+    assert_eq!(client.attempts.get(), 3);
+}
+
+#[tokio::test]
+async fn gives_async_attempts_their_own_arguments() {
+    let attempts = Cell::new(0);
+
+    assert_eq!(fetch_async(&attempts, 1, vec!["a", "b"]).await, Ok(4));
+    assert_eq!(attempts.get(), 2);
+}
+
+#[tokio::test]
+async fn borrows_async_arguments_which_cannot_be_cloned() {
+    let attempts = Cell::new(0);
+    let mut buf = Vec::new();
+
+    assert_eq!(fill_async(&attempts, &mut buf, Token(7)).await, Ok(2));
+    assert_eq!(buf, vec![7, 7]);
+}
@@ -0,0 +1,261 @@
+use std::time::Duration;
+
+/// A schedule of backoff times, which tells the retry loop
+/// how long to wait before each retry and when to give up.
+/// Schedules only describe delays; deciding whether a result
+/// is worth retrying at all, and randomizing the delays with
+/// `Jitter`, is left to the retry loop.
+pub trait Backoff {
+
+    /// Returns the time to wait after attempt number
+    /// `attempt`, counting from 1, has failed, or `None` when
+    /// no further attempts should be made.
+    fn next_delay(&self, attempt: u32) -> Option<Duration>;
+}
+
+/// Waits the same time before every retry.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConstantBackoff {
+
+    /// The time to wait before each retry.
+    pub delay: Duration,
+
+    /// The maximum number of attempts to make before giving
+    /// up.
+    pub max_retries: u8
+}
+
+impl ConstantBackoff {
+
+    /// Creates a new constant backoff.
+    pub fn new(max_retries: u8, delay: Duration) -> ConstantBackoff {
+        ConstantBackoff { delay, max_retries }
+    }
+}
+
+impl Backoff for ConstantBackoff {
+    fn next_delay(&self, attempt: u32) -> Option<Duration> {
+        if attempt >= u32::from(self.max_retries) {
+            return None;
+        }
+
+        Some(self.delay)
+    }
+}
+
+/// Waits `initial` before the first retry, and `increment`
+/// longer before each retry after that.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LinearBackoff {
+
+    /// The time to wait before the first retry.
+    pub initial: Duration,
+
+    /// The time added to the wait before each subsequent
+    /// retry.
+    pub increment: Duration,
+
+    /// The maximum number of attempts to make before giving
+    /// up.
+    pub max_retries: u8
+}
+
+impl LinearBackoff {
+
+    /// Creates a new linear backoff.
+    pub fn new(
+        max_retries: u8,
+        initial: Duration,
+        increment: Duration
+    ) -> LinearBackoff {
+        LinearBackoff { initial, increment, max_retries }
+    }
+}
+
+impl Backoff for LinearBackoff {
+    fn next_delay(&self, attempt: u32) -> Option<Duration> {
+        if attempt >= u32::from(self.max_retries) {
+            return None;
+        }
+
+        let increments = self.increment.checked_mul(attempt.saturating_sub(1))
+            .unwrap_or(Duration::MAX);
+        Some(self.initial.saturating_add(increments))
+    }
+}
+
+/// Waits `initial` before the first retry, and `multiplier`
+/// times longer before each retry after that. Whole-number
+/// multipliers give exact waits; others are computed with an
+/// f64 count of seconds.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GeometricBackoff {
+
+    /// The time to wait before the first retry.
+    pub initial: Duration,
+
+    /// The factor each wait is multiplied by to give the next.
+    pub multiplier: f32,
+
+    /// The maximum number of attempts to make before giving
+    /// up.
+    pub max_retries: u8
+}
+
+impl GeometricBackoff {
+
+    /// Creates a new geometric backoff.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `multiplier` is not a positive, finite number.
+    pub fn new(
+        max_retries: u8,
+        initial: Duration,
+        multiplier: f32
+    ) -> GeometricBackoff {
+        assert!(
+            multiplier.is_finite() && multiplier > 0.0,
+            "multiplier must be positive and finite"
+        );
+        GeometricBackoff { initial, multiplier, max_retries }
+    }
+}
+
+impl Backoff for GeometricBackoff {
+    fn next_delay(&self, attempt: u32) -> Option<Duration> {
+        if attempt >= u32::from(self.max_retries) {
+            return None;
+        }
+
+        let factor = f64::from(self.multiplier)
+            .powf(f64::from(attempt.saturating_sub(1)));
+        let delay = if factor.fract() == 0.0 && factor <= f64::from(u32::MAX) {
+            self.initial.checked_mul(factor as u32)
+        } else {
+            Duration::try_from_secs_f64(self.initial.as_secs_f64() * factor).ok()
+        };
+
+        Some(delay.unwrap_or(Duration::MAX))
+    }
+}
+
+/// Waits a multiple of `unit` following the Fibonacci
+/// sequence: one unit, one unit, two units, three units, five
+/// units, and so on. This grows more gently than an
+/// exponential backoff with an exponent of two.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FibonacciBackoff {
+
+    /// The time to wait before the first retry, which every
+    /// later wait is a multiple of.
+    pub unit: Duration,
+
+    /// The maximum number of attempts to make before giving
+    /// up.
+    pub max_retries: u8
+}
+
+impl FibonacciBackoff {
+
+    /// Creates a new Fibonacci backoff.
+    pub fn new(max_retries: u8, unit: Duration) -> FibonacciBackoff {
+        FibonacciBackoff { unit, max_retries }
+    }
+}
+
+impl Backoff for FibonacciBackoff {
+    fn next_delay(&self, attempt: u32) -> Option<Duration> {
+        if attempt >= u32::from(self.max_retries) {
+            return None;
+        }
+
+        let (mut previous, mut current) = (0u32, 1u32);
+        for _ in 1..attempt {
+            let next = previous.saturating_add(current);
+            previous = current;
+            current = next;
+        }
+
+        Some(self.unit.checked_mul(current).unwrap_or(Duration::MAX))
+    }
+}
+
+/// Waits each of `delays` in turn, giving up once they have
+/// all been used.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ListBackoff {
+
+    /// The time to wait before each retry; the first entry is
+    /// waited before the first retry.
+    pub delays: Vec<Duration>
+}
+
+impl ListBackoff {
+
+    /// Creates a new backoff waiting each of `delays` in turn.
+    pub fn new(delays: Vec<Duration>) -> ListBackoff {
+        ListBackoff { delays }
+    }
+}
+
+impl Backoff for ListBackoff {
+    fn next_delay(&self, attempt: u32) -> Option<Duration> {
+        let index = (attempt as usize).checked_sub(1)?;
+        self.delays.get(index).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::backoff::{
+        Backoff, ConstantBackoff, FibonacciBackoff, GeometricBackoff,
+        LinearBackoff, ListBackoff
+    };
+    use std::time::Duration;
+
+    #[test]
+    fn schedules_produce_expected_delays() {
+        let ms = Duration::from_millis;
+
+        assert_eq!(
+            delays(&ConstantBackoff::new(4, ms(10))),
+            vec![ms(10), ms(10), ms(10)]
+        );
+        assert_eq!(
+            delays(&LinearBackoff::new(4, ms(10), ms(5))),
+            vec![ms(10), ms(15), ms(20)]
+        );
+        assert_eq!(
+            delays(&GeometricBackoff::new(5, ms(10), 3.0)),
+            vec![ms(10), ms(30), ms(90), ms(270)]
+        );
+        assert_eq!(
+            delays(&GeometricBackoff::new(4, ms(100), 1.5)),
+            vec![ms(100), ms(150), ms(225)]
+        );
+        assert_eq!(
+            delays(&FibonacciBackoff::new(7, ms(10))),
+            vec![ms(10), ms(10), ms(20), ms(30), ms(50), ms(80)]
+        );
+        assert_eq!(
+            delays(&ListBackoff::new(vec![ms(1), ms(7), ms(3)])),
+            vec![ms(1), ms(7), ms(3)]
+        );
+    }
+
+    #[test]
+    fn schedules_saturate_instead_of_overflowing() {
+        let linear = LinearBackoff::new(255, Duration::MAX, Duration::MAX);
+        let geometric = GeometricBackoff::new(255, Duration::MAX, 2.0);
+        let fibonacci = FibonacciBackoff::new(255, Duration::MAX);
+
+        assert_eq!(linear.next_delay(200), Some(Duration::MAX));
+        assert_eq!(geometric.next_delay(200), Some(Duration::MAX));
+        assert_eq!(fibonacci.next_delay(200), Some(Duration::MAX));
+    }
+
+    fn delays(backoff: &dyn Backoff) -> Vec<Duration> {
+        (1..).map_while(|attempt| backoff.next_delay(attempt)).collect()
+    }
+}
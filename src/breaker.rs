@@ -0,0 +1,518 @@
+use std::collections::VecDeque;
+use std::error::Error;
+use std::fmt;
+use std::future::Future;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use crate::{
+    Attempts, AsyncSleeper, Clock, ConfigError, ExponentialBackoff, Next,
+    SystemClock
+};
+
+/// When a `CircuitBreaker` should open.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Threshold {
+
+    /// Open after this many failures in a row.
+    ConsecutiveFailures(u32),
+
+    /// Open once at least `rate` (between 0 and 1) of the last
+    /// `window` calls have failed. The breaker stays closed
+    /// until it has seen `window` calls.
+    FailureRate {
+
+        /// The fraction of failures which opens the breaker.
+        rate: f32,
+
+        /// The number of most recent calls to consider.
+        window: u32
+    }
+}
+
+/// The state of a `CircuitBreaker`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CircuitState {
+
+    /// Calls go through, and their failures are counted.
+    Closed,
+
+    /// Calls fail fast without being attempted, until the
+    /// cool-down period has passed.
+    Open,
+
+    /// The cool-down period has passed, and a single probe
+    /// call is let through to see whether the dependency has
+    /// recovered. If it succeeds the breaker closes, otherwise
+    /// it opens again.
+    HalfOpen
+}
+
+/// Stops calling a dependency which keeps failing, so that
+/// callers fail fast instead of each burning through a full
+/// retry schedule against it. A breaker is meant to be shared,
+/// for instance in an `Arc`, by every caller of the same
+/// dependency.
+///
+/// Every attempt made through the breaker counts towards its
+/// `Threshold`, with an `Err` counting as a failure. While the
+/// breaker is open, calls return `CircuitError::Open` without
+/// being attempted, and retries in progress give up with their
+/// last result rather than waiting for another attempt. A retry
+/// whose breaker opens while it waits returns
+/// `CircuitError::Open` instead of making its next attempt.
+pub struct CircuitBreaker {
+    threshold: Threshold,
+    cool_down: Duration,
+    clock: Box<dyn Clock + Send + Sync>,
+    state: Mutex<BreakerState>
+}
+
+#[derive(Debug)]
+struct BreakerState {
+    circuit: CircuitState,
+    opened_at: Option<Instant>,
+    probing: bool,
+    probes: u64,
+    consecutive_failures: u32,
+    outcomes: VecDeque<bool>
+}
+
+impl CircuitBreaker {
+
+    /// Creates a closed breaker which opens at `threshold` and
+    /// lets a probe through once it has been open for
+    /// `cool_down`. Refuses a `threshold` which could open the
+    /// breaker on a success, or could never open it: zero
+    /// consecutive failures, a zero window, or a rate outside
+    /// the range from just above 0 to 1.
+    pub fn new(
+        threshold: Threshold,
+        cool_down: Duration
+    ) -> Result<CircuitBreaker, ConfigError> {
+        let valid = match threshold {
+            Threshold::ConsecutiveFailures(failures) => failures > 0,
+            Threshold::FailureRate { rate, window } => {
+                window > 0 && rate > 0.0 && rate <= 1.0
+            }
+        };
+        if !valid {
+            return Err(ConfigError::InvalidThreshold(threshold));
+        }
+
+        Ok(CircuitBreaker {
+            threshold,
+            cool_down,
+            clock: Box::new(SystemClock),
+            state: Mutex::new(BreakerState {
+                circuit: CircuitState::Closed,
+                opened_at: None,
+                probing: false,
+                probes: 0,
+                consecutive_failures: 0,
+                outcomes: VecDeque::new()
+            })
+        })
+    }
+
+    /// Reads the time from `clock` instead of the system clock.
+    pub fn with_clock<
+        TClock: Clock + Send + Sync + 'static
+    > (mut self, clock: TClock) -> CircuitBreaker {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// Returns the current state of the breaker, moving it from
+    /// open to half-open if the cool-down period has passed.
+    pub fn state(&self) -> CircuitState {
+        let mut state = self.state.lock().unwrap();
+        self.cool_down(&mut state);
+        state.circuit
+    }
+
+    /// Executes an operation once, unless the breaker is open.
+    pub fn call<T, E, TRetriable>(
+        &self,
+        retriable_block: TRetriable
+    ) -> Result<T, CircuitError<E>> where TRetriable: FnOnce() -> Result<T, E> {
+        let _permit = match self.acquire() {
+            Some(permit) => permit,
+            None => return Err(CircuitError::Open)
+        };
+
+        let result = retriable_block();
+        self.record(result.is_ok());
+        result.map_err(CircuitError::Inner)
+    }
+
+    /// Executes an operation, retrying it with `backoff` for as
+    /// long as the breaker stays closed. Every attempt must be
+    /// let through by the breaker, so a retry which finds it
+    /// open after waiting returns `CircuitError::Open`.
+    pub fn retry<T, E, TRetriable>(
+        &self,
+        backoff: &ExponentialBackoff<T, E>,
+        mut retriable_block: TRetriable
+    ) -> Result<T, CircuitError<E>> where TRetriable: FnMut() -> Result<T, E> {
+        let mut attempts = Attempts::new(backoff).with_breaker(self);
+
+        loop {
+            let _permit = match self.acquire() {
+                Some(permit) => permit,
+                None => return Err(CircuitError::Open)
+            };
+
+            match attempts.next(attempts.attempt(&mut retriable_block)) {
+                Next::Wait(backoff_time) => backoff.sleeper.sleep(backoff_time),
+                Next::Finish(report) => {
+                    return report.result.map_err(CircuitError::Inner);
+                }
+            }
+        }
+    }
+
+    /// Executes an asynchronous operation, retrying it with
+    /// `backoff` for as long as the breaker stays closed, and
+    /// waiting between attempts with `sleeper`. Like `retry`,
+    /// every attempt must be let through by the breaker.
+    pub async fn retry_async<T, E, TSleeper, TRetriable, TFuture>(
+        &self,
+        backoff: &ExponentialBackoff<T, E>,
+        sleeper: &TSleeper,
+        mut retriable_block: TRetriable
+    ) -> Result<T, CircuitError<E>> where
+        TSleeper: AsyncSleeper + ?Sized,
+        TRetriable: FnMut() -> TFuture,
+        TFuture: Future<Output = Result<T, E>>
+    {
+        let mut attempts = Attempts::new(backoff).with_breaker(self);
+
+        loop {
+            let _permit = match self.acquire() {
+                Some(permit) => permit,
+                None => return Err(CircuitError::Open)
+            };
+
+            match attempts.next(attempts.instrument(retriable_block()).await) {
+                Next::Wait(backoff_time) => sleeper.sleep(backoff_time).await,
+                Next::Finish(report) => {
+                    return report.result.map_err(CircuitError::Inner);
+                }
+            }
+        }
+    }
+
+    /// Asks to make a call, which is allowed while the breaker
+    /// is closed, and for a single probe while it is half-open.
+    /// The permit must be held until the attempt's outcome has
+    /// been recorded.
+    fn acquire(&self) -> Option<Permit<'_>> {
+        let mut state = self.state.lock().unwrap();
+        self.cool_down(&mut state);
+
+        match state.circuit {
+            CircuitState::Closed => Some(Permit { breaker: self, probe: None }),
+            CircuitState::Open => None,
+            CircuitState::HalfOpen if state.probing => None,
+            CircuitState::HalfOpen => {
+                state.probing = true;
+                state.probes = state.probes.wrapping_add(1);
+                Some(Permit { breaker: self, probe: Some(state.probes) })
+            }
+        }
+    }
+
+    /// Counts the outcome of an attempt made through the
+    /// breaker.
+    pub(crate) fn record(&self, success: bool) {
+        let mut state = self.state.lock().unwrap();
+
+        if state.circuit == CircuitState::HalfOpen {
+            state.probing = false;
+            if success {
+                state.circuit = CircuitState::Closed;
+                state.consecutive_failures = 0;
+                state.outcomes.clear();
+            } else {
+                self.open(&mut state);
+            }
+            return;
+        }
+
+        if success {
+            state.consecutive_failures = 0;
+        } else {
+            state.consecutive_failures += 1;
+        }
+
+        let tripped = match self.threshold {
+            Threshold::ConsecutiveFailures(failures) => {
+                state.consecutive_failures >= failures
+            },
+            Threshold::FailureRate { rate, window } => {
+                state.outcomes.push_back(success);
+                while state.outcomes.len() > window as usize {
+                    state.outcomes.pop_front();
+                }
+
+                let failures = state.outcomes.iter()
+                    .filter(|success| !**success)
+                    .count();
+                state.outcomes.len() == window as usize
+                    && failures as f32 >= rate * window as f32
+            }
+        };
+
+        if tripped && state.circuit == CircuitState::Closed {
+            self.open(&mut state);
+        }
+    }
+
+    /// Whether retries in progress should give up rather than
+    /// waiting for another attempt.
+    pub(crate) fn is_open(&self) -> bool {
+        self.state.lock().unwrap().circuit == CircuitState::Open
+    }
+
+    fn open(&self, state: &mut BreakerState) {
+        state.circuit = CircuitState::Open;
+        state.opened_at = Some(self.clock.now());
+        state.consecutive_failures = 0;
+        state.outcomes.clear();
+    }
+
+    fn cool_down(&self, state: &mut BreakerState) {
+        if state.circuit != CircuitState::Open {
+            return;
+        }
+
+        let opened_at = state.opened_at.unwrap_or_else(|| self.clock.now());
+        let open_for = self.clock.now().saturating_duration_since(opened_at);
+        if open_for >= self.cool_down {
+            state.circuit = CircuitState::HalfOpen;
+            state.probing = false;
+        }
+    }
+}
+
+/// An attempt let through by a `CircuitBreaker`. A probe whose
+/// outcome is never recorded, because its attempt panicked or
+/// its future was dropped, is given up when the permit drops,
+/// so the breaker lets another probe through rather than
+/// waiting for it forever.
+struct Permit<'a> {
+    breaker: &'a CircuitBreaker,
+    probe: Option<u64>
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        if let Some(probe) = self.probe {
+            let mut state = self.breaker.state.lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            if state.probing && state.probes == probe {
+                state.probing = false;
+            }
+        }
+    }
+}
+
+/// The error returned by calls made through a
+/// `CircuitBreaker`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum CircuitError<E> {
+
+    /// The breaker was open, so the operation was not
+    /// attempted.
+    Open,
+
+    /// The operation was attempted, and failed with this error.
+    Inner(E)
+}
+
+impl <E: fmt::Display> fmt::Display for CircuitError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CircuitError::Open => write!(f, "circuit breaker is open"),
+            CircuitError::Inner(error) => error.fmt(f)
+        }
+    }
+}
+
+impl <E: Error + 'static> Error for CircuitError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CircuitError::Open => None,
+            CircuitError::Inner(error) => Some(error)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        CircuitBreaker, CircuitError, CircuitState, ConfigError,
+        ExponentialBackoff, MockClock, Sleeper, Threshold
+    };
+    use std::future::{pending, poll_fn, Future};
+    use std::panic::{self, AssertUnwindSafe};
+    use std::sync::Arc;
+    use std::task::Poll;
+    use std::time::Duration;
+
+    #[test]
+    fn opens_fails_fast_and_probes_recovery() {
+        let clock = MockClock::new();
+        let breaker = CircuitBreaker::new(
+            Threshold::ConsecutiveFailures(3), Duration::from_secs(10)
+        ).unwrap().with_clock(clock.clone());
+        let backoff = test_backoff(&clock);
+        let mut attempts = 0;
+
+        let result = breaker.retry(&backoff, || {
+            attempts += 1;
+            Err::<(), u32>(attempts)
+        });
+
+        // the breaker opened on the third failure, cutting the
+        // retries short
+        assert_eq!(result, Err(CircuitError::Inner(3)));
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert_eq!(
+            breaker.retry(&backoff, || Ok::<(), u32>(())),
+            Err(CircuitError::Open)
+        );
+
+        clock.advance(Duration::from_secs(10));
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        assert_eq!(
+            breaker.call(|| Err::<(), u32>(0)),
+            Err(CircuitError::Inner(0))
+        );
+        assert_eq!(breaker.state(), CircuitState::Open);
+
+        clock.advance(Duration::from_secs(10));
+        assert_eq!(breaker.call(|| Ok::<(), u32>(())), Ok(()));
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+
+    #[test]
+    fn opens_at_failure_rate() {
+        let breaker = CircuitBreaker::new(
+            Threshold::FailureRate { rate: 0.5, window: 4 },
+            Duration::from_secs(10)
+        ).unwrap();
+
+        for success in [true, false, true] {
+            let _ = breaker.call(|| if success { Ok(()) } else { Err(()) });
+        }
+        assert_eq!(breaker.state(), CircuitState::Closed);
+
+        let _ = breaker.call(|| Err::<(), ()>(()));
+        assert_eq!(breaker.state(), CircuitState::Open);
+    }
+
+    /// Sleeps by having other callers fail until the breaker
+    /// opens.
+    struct TrippingSleeper(Arc<CircuitBreaker>);
+
+    impl Sleeper for TrippingSleeper {
+        fn sleep(&self, _duration: Duration) {
+            while self.0.state() == CircuitState::Closed {
+                let _ = self.0.call(|| Err::<(), ()>(()));
+            }
+        }
+    }
+
+    #[test]
+    fn asks_the_breaker_before_every_attempt() {
+        let breaker = Arc::new(CircuitBreaker::new(
+            Threshold::ConsecutiveFailures(3), Duration::from_secs(10)
+        ).unwrap());
+        let backoff = ExponentialBackoff::new(
+            7, Duration::ZERO, Duration::from_millis(1), 2.0,
+            |result: &Result<(), u32>| result.is_err()
+        ).with_sleeper(TrippingSleeper(breaker.clone()));
+        let mut attempts = 0;
+
+        let result = breaker.retry(&backoff, || {
+            attempts += 1;
+            Err::<(), u32>(attempts)
+        });
+
+        // the breaker opened while the retry slept, so the
+        // second attempt was never made
+        assert_eq!(result, Err(CircuitError::Open));
+        assert_eq!(attempts, 1);
+    }
+
+    #[test]
+    fn rejects_thresholds_which_cannot_work() {
+        let cool_down = Duration::from_secs(10);
+        let tripped_by_success = Threshold::ConsecutiveFailures(0);
+        let never_tripped = Threshold::FailureRate { rate: f32::NAN, window: 4 };
+
+        assert_eq!(
+            CircuitBreaker::new(tripped_by_success, cool_down).err(),
+            Some(ConfigError::InvalidThreshold(tripped_by_success))
+        );
+        assert_eq!(
+            CircuitBreaker::new(never_tripped, cool_down).err().map(|e| e.to_string()),
+            Some("circuit breaker threshold FailureRate { rate: NaN, window: 4 } \
+                can never open or would open on a success".to_string())
+        );
+    }
+
+    #[test]
+    fn gives_up_on_a_probe_which_panics() {
+        let clock = MockClock::new();
+        let breaker = tripped_breaker(&clock);
+
+        let probe = panic::catch_unwind(AssertUnwindSafe(|| {
+            breaker.call(|| -> Result<(), ()> { panic!("probe failed") })
+        }));
+
+        assert!(probe.is_err());
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        assert_eq!(breaker.call(|| Ok::<(), ()>(())), Ok(()));
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+
+    #[tokio::test]
+    async fn gives_up_on_a_probe_which_is_dropped() {
+        let clock = MockClock::new();
+        let breaker = tripped_breaker(&clock);
+        let backoff = test_backoff(&clock);
+
+        let mut probe = Box::pin(
+            breaker.retry_async(&backoff, &clock, pending::<Result<(), u32>>)
+        );
+        let polled = poll_fn(|cx| Poll::Ready(probe.as_mut().poll(cx))).await;
+        assert!(polled.is_pending());
+        drop(probe);
+
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        assert_eq!(breaker.call(|| Ok::<(), ()>(())), Ok(()));
+        assert_eq!(breaker.state(), CircuitState::Closed);
+    }
+
+    /// A breaker which has opened and cooled down, ready to let
+    /// a probe through.
+    fn tripped_breaker(clock: &MockClock) -> CircuitBreaker {
+        let breaker = CircuitBreaker::new(
+            Threshold::ConsecutiveFailures(1), Duration::from_secs(10)
+        ).unwrap().with_clock(clock.clone());
+
+        let _ = breaker.call(|| Err::<(), ()>(()));
+        clock.advance(Duration::from_secs(10));
+        breaker
+    }
+
+    fn test_backoff(clock: &MockClock) -> ExponentialBackoff<(), u32> {
+        ExponentialBackoff::new(
+            7, Duration::ZERO, Duration::from_millis(1), 2.0,
+            |result: &Result<(), u32>| result.is_err()
+        ).with_sleeper(clock.clone())
+    }
+}
@@ -0,0 +1,109 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Milli-tokens per token, so fractional deposits can be kept
+/// in an integer.
+const SCALE: u64 = 1000;
+
+/// Limits retries to a fraction of the traffic they are
+/// retrying, in the manner of gRPC's and Finagle's retry
+/// throttling. It is a token bucket: every successful attempt
+/// deposits `deposit_per_success` tokens, up to `max_tokens`,
+/// and every retry withdraws one. Once the bucket is empty,
+/// retries are refused, and the last result is returned
+/// instead. In the long run this allows about one retry per
+/// `1 / deposit_per_success` successes, plus a burst of
+/// `max_tokens`.
+///
+/// A budget is meant to be shared, for instance in an `Arc`,
+/// by every backoff calling the same upstream, so that an
+/// outage cannot multiply the load on it by `max_retries`.
+#[derive(Debug)]
+pub struct RetryBudget {
+    max_tokens: u64,
+    deposit: u64,
+    tokens: AtomicU64
+}
+
+impl RetryBudget {
+
+    /// Creates a full budget holding `max_tokens` tokens, which
+    /// earns back `deposit_per_success` tokens for every
+    /// successful attempt.
+    pub fn new(max_tokens: u32, deposit_per_success: f32) -> RetryBudget {
+        let max_tokens = u64::from(max_tokens) * SCALE;
+
+        RetryBudget {
+            max_tokens,
+            deposit: (deposit_per_success.max(0.0) * SCALE as f32) as u64,
+            tokens: AtomicU64::new(max_tokens)
+        }
+    }
+
+    /// Returns the number of whole retries the budget can
+    /// currently pay for.
+    pub fn available(&self) -> u32 {
+        (self.tokens.load(Ordering::Relaxed) / SCALE) as u32
+    }
+
+    /// Earns tokens for a successful attempt.
+    pub fn deposit(&self) {
+        let _ = self.tokens.fetch_update(
+            Ordering::Relaxed, Ordering::Relaxed, |tokens| {
+                Some(tokens.saturating_add(self.deposit).min(self.max_tokens))
+            }
+        );
+    }
+
+    /// Spends a token on a retry, returning `false` without
+    /// spending anything if the budget cannot afford it.
+    pub fn try_withdraw(&self) -> bool {
+        self.tokens.fetch_update(
+            Ordering::Relaxed, Ordering::Relaxed, |tokens| {
+                tokens.checked_sub(SCALE)
+            }
+        ).is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ExponentialBackoff, MockClock, RetryBudget};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn refuses_retries_once_spent() {
+        let budget = Arc::new(RetryBudget::new(2, 0.5));
+        let backoff = ExponentialBackoff::new(
+            7, Duration::ZERO, Duration::from_millis(1), 1.0,
+            |result: &Result<(), ()>| result.is_err()
+        )
+            .with_sleeper(MockClock::new())
+            .with_retry_budget(budget.clone());
+
+        let report = backoff.retry_with_report(|| Err(()));
+        assert_eq!(report.attempts, 3);
+        assert_eq!(budget.available(), 0);
+
+        let report = backoff.retry_with_report(|| Err(()));
+        assert_eq!(report.attempts, 1);
+
+        assert!(backoff.retry(|| Ok(())).is_ok());
+        assert!(backoff.retry(|| Ok(())).is_ok());
+        assert_eq!(budget.available(), 1);
+
+        let report = backoff.retry_with_report(|| Err(()));
+        assert_eq!(report.attempts, 2);
+    }
+
+    #[test]
+    fn deposits_are_capped() {
+        let budget = RetryBudget::new(3, 1.0);
+
+        for _ in 0..10 {
+            budget.deposit();
+        }
+
+        assert_eq!(budget.available(), 3);
+    }
+}
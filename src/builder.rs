@@ -0,0 +1,303 @@
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::{
+    exponential_delay, Backoff, Clock, Decision, ExponentialBackoff, Jitter,
+    RetryBudget, Sleeper, Threshold
+};
+
+/// Builds an `ExponentialBackoff` one named setting at a time,
+/// checking the configuration makes sense before handing it
+/// over. Settings left alone take the values used by
+/// `ExponentialBackoff::new_with_defaults`, and the backoff
+/// retries any `Err` unless told otherwise with
+/// `should_retry`.
+pub struct ExponentialBackoffBuilder<T, E> {
+    backoff: ExponentialBackoff<T, E>
+}
+
+impl <T, E> ExponentialBackoffBuilder<T, E> {
+    pub(crate) fn new() -> ExponentialBackoffBuilder<T, E> {
+        ExponentialBackoffBuilder {
+            backoff: ExponentialBackoff::new_with_defaults(
+                |result: &Result<T, E>| result.is_err()
+            )
+        }
+    }
+
+    /// Sets the block deciding whether a result ought to be
+    /// retried. It may return either a `bool` or a `Decision`.
+    pub fn should_retry<
+        TDecision: Into<Decision>,
+        TShouldRetry: Fn(&Result<T, E>) -> TDecision + Send + Sync + 'static
+    > (mut self, should_retry: TShouldRetry) -> ExponentialBackoffBuilder<T, E> {
+        self.backoff.should_retry = Box::new(
+            move |result| should_retry(result).into()
+        );
+        self
+    }
+
+    /// Sets the maximum number of attempts to make before
+    /// giving up, which must be at least one.
+    pub fn max_retries(mut self, max_retries: u8) -> ExponentialBackoffBuilder<T, E> {
+        self.backoff.max_retries = max_retries;
+        self
+    }
+
+    /// Sets the time added to every backoff time.
+    pub fn constant(mut self, constant: Duration) -> ExponentialBackoffBuilder<T, E> {
+        self.backoff.constant = constant;
+        self
+    }
+
+    /// Sets the time which is multiplied by the exponentiated
+    /// attempt number, which is the backoff time before the
+    /// first retry when `constant` is zero.
+    pub fn base(mut self, base: Duration) -> ExponentialBackoffBuilder<T, E> {
+        self.backoff.coefficient = base;
+        self
+    }
+
+    /// Sets the exponent to raise the attempt number to, which
+    /// must be finite.
+    pub fn exponent(mut self, exponent: f32) -> ExponentialBackoffBuilder<T, E> {
+        self.backoff.exponent = exponent;
+        self
+    }
+
+    /// Sets the randomness to mix into each backoff time.
+    pub fn jitter(mut self, jitter: Jitter) -> ExponentialBackoffBuilder<T, E> {
+        self.backoff.jitter = jitter;
+        self
+    }
+
+    /// Seeds the jitter's random number generator, making the
+    /// backoff times of every retry repeatable.
+    pub fn jitter_seed(mut self, seed: u64) -> ExponentialBackoffBuilder<T, E> {
+        self.backoff.jitter_seed = Some(seed);
+        self
+    }
+
+    /// Follows `schedule` instead of the exponential formula.
+    pub fn schedule<
+        TBackoff: Backoff + Send + Sync + 'static
+    > (mut self, schedule: TBackoff) -> ExponentialBackoffBuilder<T, E> {
+        self.backoff.schedule = Some(Box::new(schedule));
+        self
+    }
+
+    /// Caps the time waited before any single retry.
+    pub fn max_delay(mut self, max_delay: Duration) -> ExponentialBackoffBuilder<T, E> {
+        self.backoff.max_delay = Some(max_delay);
+        self
+    }
+
+    /// Bounds the total time spent retrying.
+    pub fn max_elapsed(mut self, max_elapsed: Duration) -> ExponentialBackoffBuilder<T, E> {
+        self.backoff.max_elapsed = Some(max_elapsed);
+        self
+    }
+
+    /// Calls `on_retry` before waiting to retry a failed
+    /// attempt.
+    pub fn on_retry<
+        TOnRetry: Fn(u32, &Result<T, E>, Duration) + Send + Sync + 'static
+    > (mut self, on_retry: TOnRetry) -> ExponentialBackoffBuilder<T, E> {
+        self.backoff.on_retry = Some(Box::new(on_retry));
+        self
+    }
+
+    /// Calls `on_give_up` when retrying stops with an error.
+    pub fn on_give_up<
+        TOnGiveUp: Fn(u32, &E) + Send + Sync + 'static
+    > (mut self, on_give_up: TOnGiveUp) -> ExponentialBackoffBuilder<T, E> {
+        self.backoff.on_give_up = Some(Box::new(on_give_up));
+        self
+    }
+
+    /// Calls `on_success` when retrying stops with a success.
+    pub fn on_success<
+        TOnSuccess: Fn(u32, &T) + Send + Sync + 'static
+    > (mut self, on_success: TOnSuccess) -> ExponentialBackoffBuilder<T, E> {
+        self.backoff.on_success = Some(Box::new(on_success));
+        self
+    }
+
+    /// Reads the time from `clock` instead of the system clock.
+    pub fn clock<
+        TClock: Clock + Send + Sync + 'static
+    > (mut self, clock: TClock) -> ExponentialBackoffBuilder<T, E> {
+        self.backoff.clock = Box::new(clock);
+        self
+    }
+
+    /// Waits between blocking attempts with `sleeper` instead
+    /// of `std::thread::sleep`.
+    pub fn sleeper<
+        TSleeper: Sleeper + Send + Sync + 'static
+    > (mut self, sleeper: TSleeper) -> ExponentialBackoffBuilder<T, E> {
+        self.backoff.sleeper = Box::new(sleeper);
+        self
+    }
+
+    /// Pays for every retry from `retry_budget`, giving up once
+    /// it is spent.
+    pub fn retry_budget(
+        mut self,
+        retry_budget: Arc<RetryBudget>
+    ) -> ExponentialBackoffBuilder<T, E> {
+        self.backoff.retry_budget = Some(retry_budget);
+        self
+    }
+
+    /// Names the operation being retried, to label its metrics
+    /// and traces.
+    pub fn operation<
+        TOperation: Into<String>
+    > (mut self, operation: TOperation) -> ExponentialBackoffBuilder<T, E> {
+        self.backoff.operation = Some(operation.into());
+        self
+    }
+
+    /// Records the message of each error in traces, as well as
+    /// its type, when the `tracing` feature is enabled.
+    pub fn error_messages(mut self) -> ExponentialBackoffBuilder<T, E> where
+        E: std::fmt::Display
+    {
+        self.backoff = self.backoff.with_error_messages();
+        self
+    }
+
+    /// Checks the configuration and builds the backoff.
+    pub fn build(self) -> Result<ExponentialBackoff<T, E>, ConfigError> {
+        let backoff = self.backoff;
+
+        if backoff.max_retries == 0 {
+            return Err(ConfigError::ZeroRetries);
+        }
+
+        if !backoff.exponent.is_finite() {
+            return Err(ConfigError::InvalidExponent(backoff.exponent));
+        }
+
+        for attempt in 1..u32::from(backoff.max_retries) {
+            let delay = exponential_delay(
+                backoff.constant, backoff.coefficient, backoff.exponent, attempt
+            );
+            if delay.is_none() {
+                return Err(ConfigError::DelayOverflow { attempt });
+            }
+        }
+
+        Ok(backoff)
+    }
+
+    /// Builds the backoff without checking the configuration.
+    pub(crate) fn build_unchecked(self) -> ExponentialBackoff<T, E> {
+        self.backoff
+    }
+}
+
+/// A reason an `ExponentialBackoffBuilder` refused to build a
+/// backoff.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConfigError {
+
+    /// `max_retries` was zero, so the operation would never be
+    /// attempted.
+    ZeroRetries,
+
+    /// The exponent was NaN or infinite.
+    InvalidExponent(f32),
+
+    /// The multiplier of a geometric schedule was not a
+    /// positive, finite number.
+    InvalidMultiplier(f32),
+
+    /// The threshold of a `CircuitBreaker` could never open
+    /// it, or would open it on a success.
+    InvalidThreshold(Threshold),
+
+    /// The backoff time for this attempt is too large to
+    /// represent.
+    DelayOverflow {
+
+        /// The attempt whose backoff time overflowed.
+        attempt: u32
+    }
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::ZeroRetries => write!(
+                f, "max_retries must be at least 1"
+            ),
+            ConfigError::InvalidExponent(exponent) => write!(
+                f, "exponent must be finite, but was {}", exponent
+            ),
+            ConfigError::InvalidMultiplier(multiplier) => write!(
+                f, "multiplier must be positive and finite, but was {}", multiplier
+            ),
+            ConfigError::InvalidThreshold(threshold) => write!(
+                f,
+                "circuit breaker threshold {:?} can never open or would open on a success",
+                threshold
+            ),
+            ConfigError::DelayOverflow { attempt } => write!(
+                f, "backoff time for attempt {} is too large", attempt
+            )
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Backoff, ConfigError, Decision, ExponentialBackoff};
+    use std::time::Duration;
+
+    #[test]
+    fn builds_named_settings() {
+        let backoff = ExponentialBackoff::<(), ()>::builder()
+            .max_retries(3)
+            .constant(Duration::from_millis(5))
+            .base(Duration::from_millis(100))
+            .exponent(2.0)
+            .build()
+            .unwrap();
+
+        assert_eq!(backoff.next_delay(1), Some(Duration::from_millis(105)));
+        assert_eq!(backoff.next_delay(2), Some(Duration::from_millis(405)));
+        assert_eq!(backoff.next_delay(3), None);
+        assert_eq!((backoff.should_retry)(&Err(())), Decision::Retry);
+        assert_eq!((backoff.should_retry)(&Ok(())), Decision::Stop);
+    }
+
+    #[test]
+    fn rejects_invalid_settings() {
+        let build = |max_retries, exponent| {
+            ExponentialBackoff::<(), ()>::builder()
+                .max_retries(max_retries)
+                .exponent(exponent)
+                .build()
+                .err()
+        };
+
+        assert_eq!(build(0, 1.0), Some(ConfigError::ZeroRetries));
+        assert_eq!(build(3, f32::NAN).map(|e| e.to_string()), Some(
+            "exponent must be finite, but was NaN".to_string()
+        ));
+        assert_eq!(
+            build(3, f32::INFINITY),
+            Some(ConfigError::InvalidExponent(f32::INFINITY))
+        );
+        assert_eq!(
+            build(200, 30.0),
+            Some(ConfigError::DelayOverflow { attempt: 5 })
+        );
+    }
+}
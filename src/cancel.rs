@@ -0,0 +1,132 @@
+use std::error::Error;
+use std::fmt;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// Cancels retries from another thread. Cancelling wakes any
+/// retry sleeping on the token straight away, rather than
+/// leaving it to sleep out its backoff time. Clones share the
+/// same cancellation, so one can be handed to each worker and
+/// another kept to cancel them all on shutdown.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    inner: Arc<(Mutex<bool>, Condvar)>
+}
+
+impl CancellationToken {
+
+    /// Creates a token which has not been cancelled.
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    /// Cancels every retry using this token, waking any which
+    /// are sleeping.
+    pub fn cancel(&self) {
+        let (cancelled, condvar) = &*self.inner;
+        *cancelled.lock().unwrap() = true;
+        condvar.notify_all();
+    }
+
+    /// Whether the token has been cancelled.
+    pub fn is_cancelled(&self) -> bool {
+        *self.inner.0.lock().unwrap()
+    }
+
+    /// Blocks until `duration` has elapsed or the token is
+    /// cancelled, whichever comes first, returning whether it
+    /// was cancelled.
+    pub fn wait_timeout(&self, duration: Duration) -> bool {
+        let (cancelled, condvar) = &*self.inner;
+        let deadline = Instant::now().checked_add(duration);
+        let mut guard = cancelled.lock().unwrap();
+
+        while !*guard {
+            let remaining = match deadline {
+                Some(deadline) => deadline.saturating_duration_since(Instant::now()),
+                None => duration
+            };
+            if remaining == Duration::ZERO {
+                break;
+            }
+            guard = condvar.wait_timeout(guard, remaining).unwrap().0;
+        }
+
+        *guard
+    }
+}
+
+/// The error returned by `ExponentialBackoff::retry_cancellable`.
+#[derive(Debug, PartialEq, Eq)]
+pub enum CancelError<E> {
+
+    /// The retry was cancelled before it finished.
+    Cancelled,
+
+    /// The retry finished with this error.
+    Inner(E)
+}
+
+impl <E: fmt::Display> fmt::Display for CancelError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CancelError::Cancelled => write!(f, "retry was cancelled"),
+            CancelError::Inner(error) => error.fmt(f)
+        }
+    }
+}
+
+impl <E: Error + 'static> Error for CancelError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CancelError::Cancelled => None,
+            CancelError::Inner(error) => Some(error)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CancelError, CancellationToken, ExponentialBackoff};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn cancelling_wakes_a_sleeping_retry() {
+        let token = CancellationToken::new();
+        let backoff = ExponentialBackoff::new(
+            7, Duration::from_secs(60), Duration::ZERO, 1.0,
+            |result: &Result<(), ()>| result.is_err()
+        );
+
+        let canceller = token.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(20));
+            canceller.cancel();
+        });
+
+        let started = Instant::now();
+        let result = backoff.retry_cancellable(&token, || Err(()));
+
+        assert_eq!(result, Err(CancelError::Cancelled));
+        assert!(started.elapsed() < Duration::from_secs(30));
+    }
+
+    #[test]
+    fn cancelled_token_prevents_attempts() {
+        let token = CancellationToken::new();
+        token.cancel();
+        let backoff = ExponentialBackoff::new_with_defaults(
+            |result: &Result<(), ()>| result.is_err()
+        );
+        let mut attempts = 0;
+
+        let result = backoff.retry_cancellable(&token, || {
+            attempts += 1;
+            Ok(())
+        });
+
+        assert_eq!(result, Err(CancelError::Cancelled));
+        assert_eq!(attempts, 0);
+    }
+}
@@ -0,0 +1,134 @@
+use std::future::{ready, Ready};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::cancel::CancellationToken;
+use crate::sleep::{AsyncSleeper, Sleeper};
+
+/// A source of the current time, which the retry loop uses to
+/// keep track of `max_elapsed`.
+pub trait Clock {
+
+    /// Returns the current time.
+    fn now(&self) -> Instant;
+}
+
+/// The real clock, read with `Instant::now`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A virtual clock for tests, which only moves when told to.
+/// It is also a `Sleeper` and an `AsyncSleeper`: rather than
+/// waiting, sleeping records the requested time and advances
+/// the clock by it instantly, so tests can check exactly what
+/// a retry would have waited without waiting for it. Clones
+/// share the same time and record of sleeps.
+#[derive(Clone, Debug)]
+pub struct MockClock {
+    state: Arc<Mutex<MockClockState>>
+}
+
+#[derive(Debug)]
+struct MockClockState {
+    now: Instant,
+    sleeps: Vec<Duration>
+}
+
+impl MockClock {
+
+    /// Creates a clock reading the current time, which will
+    /// stand still until advanced or slept on.
+    pub fn new() -> MockClock {
+        MockClock {
+            state: Arc::new(Mutex::new(MockClockState {
+                now: Instant::now(),
+                sleeps: Vec::new()
+            }))
+        }
+    }
+
+    /// Moves the clock forward by `duration` without recording
+    /// a sleep, as if an attempt took that long.
+    pub fn advance(&self, duration: Duration) {
+        self.state.lock().unwrap().now += duration;
+    }
+
+    /// Returns every sleep requested so far, in order.
+    pub fn sleeps(&self) -> Vec<Duration> {
+        self.state.lock().unwrap().sleeps.clone()
+    }
+
+    fn record_sleep(&self, duration: Duration) {
+        let mut state = self.state.lock().unwrap();
+        state.now += duration;
+        state.sleeps.push(duration);
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> MockClock {
+        MockClock::new()
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        self.state.lock().unwrap().now
+    }
+}
+
+impl Sleeper for MockClock {
+    fn sleep(&self, duration: Duration) {
+        self.record_sleep(duration);
+    }
+
+    fn sleep_cancellable(
+        &self,
+        duration: Duration,
+        token: &CancellationToken
+    ) -> bool {
+        if token.is_cancelled() {
+            return true;
+        }
+
+        self.record_sleep(duration);
+        token.is_cancelled()
+    }
+}
+
+impl AsyncSleeper for MockClock {
+    type Sleep = Ready<()>;
+
+    fn sleep(&self, duration: Duration) -> Ready<()> {
+        self.record_sleep(duration);
+        ready(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::clock::{Clock, MockClock};
+    use crate::sleep::Sleeper;
+    use std::time::Duration;
+
+    #[test]
+    fn sleeping_advances_virtual_time() {
+        let clock = MockClock::new();
+        let start = clock.now();
+
+        Sleeper::sleep(&clock.clone(), Duration::from_secs(60));
+        clock.advance(Duration::from_secs(1));
+        Sleeper::sleep(&clock, Duration::from_secs(120));
+
+        assert_eq!(clock.now() - start, Duration::from_secs(181));
+        assert_eq!(clock.sleeps(), vec![
+            Duration::from_secs(60), Duration::from_secs(120)
+        ]);
+    }
+}
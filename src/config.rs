@@ -0,0 +1,301 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ConfigError, ConstantBackoff, Decision, ExponentialBackoff,
+    FibonacciBackoff, GeometricBackoff, Jitter, LinearBackoff
+};
+
+/// Which schedule a `RetryPolicyConfig` waits between retries.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScheduleKind {
+
+    /// Wait *an^b+c*, where *a* is `base`, *b* is `exponent`
+    /// and *c* is `constant`.
+    #[default]
+    Exponential,
+
+    /// Wait `base` before every retry.
+    Constant,
+
+    /// Wait `base` before the first retry, and `base` longer
+    /// before each one after.
+    Linear,
+
+    /// Wait `base` before the first retry, and `multiplier`
+    /// times longer before each one after.
+    Geometric,
+
+    /// Wait `base` times successive Fibonacci numbers.
+    Fibonacci
+}
+
+/// The settings of a retry policy, in a form which can be kept
+/// in configuration files. Durations are written the
+/// human-readable way, such as `"250ms"` or `"1m 30s"`, and
+/// settings left out take the values used by
+/// `ExponentialBackoff::new_with_defaults`.
+///
+/// ```toml
+/// max_retries = 5
+/// schedule = "exponential"
+/// base = "100ms"
+/// exponent = 2.0
+/// max_delay = "10s"
+/// jitter = "full"
+/// max_elapsed = "1m"
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetryPolicyConfig {
+
+    /// The maximum number of attempts to make.
+    pub max_retries: u8,
+
+    /// The schedule to wait between retries.
+    pub schedule: ScheduleKind,
+
+    /// The unit of every schedule's backoff times.
+    #[serde(with = "humantime_serde")]
+    pub base: Duration,
+
+    /// The exponent to raise the attempt number to, for the
+    /// exponential schedule.
+    pub exponent: f32,
+
+    /// The factor each backoff time is multiplied by to give
+    /// the next, for the geometric schedule.
+    pub multiplier: f32,
+
+    /// The time added to every backoff time, for the
+    /// exponential schedule.
+    #[serde(with = "humantime_serde")]
+    pub constant: Duration,
+
+    /// Caps the time waited before any single retry.
+    #[serde(with = "humantime_serde")]
+    pub max_delay: Option<Duration>,
+
+    /// The randomness to mix into each backoff time.
+    pub jitter: Jitter,
+
+    /// Seeds the jitter, making backoff times repeatable.
+    pub jitter_seed: Option<u64>,
+
+    /// Bounds the total time spent retrying.
+    #[serde(with = "humantime_serde")]
+    pub max_elapsed: Option<Duration>
+}
+
+impl Default for RetryPolicyConfig {
+    fn default() -> RetryPolicyConfig {
+        RetryPolicyConfig {
+            max_retries: 7,
+            schedule: ScheduleKind::Exponential,
+            base: Duration::from_secs(1),
+            exponent: 0.5,
+            multiplier: 2.0,
+            constant: Duration::ZERO,
+            max_delay: None,
+            jitter: Jitter::None,
+            jitter_seed: None,
+            max_elapsed: None
+        }
+    }
+}
+
+impl RetryPolicyConfig {
+
+    /// Builds a backoff following this configuration, retrying
+    /// results as `should_retry` decides.
+    pub fn build<T, E, TDecision, TShouldRetry>(
+        &self,
+        should_retry: TShouldRetry
+    ) -> Result<ExponentialBackoff<T, E>, ConfigError> where
+        TDecision: Into<Decision>,
+        TShouldRetry: Fn(&Result<T, E>) -> TDecision + Send + Sync + 'static
+    {
+        let mut builder = ExponentialBackoff::builder()
+            .should_retry(should_retry)
+            .max_retries(self.max_retries)
+            .base(self.base)
+            .exponent(self.exponent)
+            .constant(self.constant)
+            .jitter(self.jitter);
+
+        builder = match self.schedule {
+            ScheduleKind::Exponential => builder,
+            ScheduleKind::Constant => builder.schedule(
+                ConstantBackoff::new(self.max_retries, self.base)
+            ),
+            ScheduleKind::Linear => builder.schedule(
+                LinearBackoff::new(self.max_retries, self.base, self.base)
+            ),
+            ScheduleKind::Geometric => {
+                if !(self.multiplier.is_finite() && self.multiplier > 0.0) {
+                    return Err(ConfigError::InvalidMultiplier(self.multiplier));
+                }
+                builder.schedule(
+                    GeometricBackoff::new(self.max_retries, self.base, self.multiplier)
+                )
+            },
+            ScheduleKind::Fibonacci => builder.schedule(
+                FibonacciBackoff::new(self.max_retries, self.base)
+            )
+        };
+
+        if let Some(max_delay) = self.max_delay {
+            builder = builder.max_delay(max_delay);
+        }
+        if let Some(seed) = self.jitter_seed {
+            builder = builder.jitter_seed(seed);
+        }
+        if let Some(max_elapsed) = self.max_elapsed {
+            builder = builder.max_elapsed(max_elapsed);
+        }
+
+        builder.build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        Backoff, ConfigError, Jitter, RetryPolicyConfig, ScheduleKind
+    };
+    use std::time::Duration;
+
+    fn expected() -> RetryPolicyConfig {
+        RetryPolicyConfig {
+            max_retries: 5,
+            schedule: ScheduleKind::Linear,
+            base: Duration::from_millis(100),
+            max_delay: Some(Duration::from_secs(90)),
+            jitter: Jitter::Full,
+            max_elapsed: Some(Duration::from_secs(300)),
+            ..RetryPolicyConfig::default()
+        }
+    }
+
+    #[test]
+    fn loads_from_toml() {
+        let config: RetryPolicyConfig = toml::from_str(r#"
+            max_retries = 5
+            schedule = "linear"
+            base = "100ms"
+            max_delay = "1m 30s"
+            jitter = "full"
+            max_elapsed = "5m"
+        "#).unwrap();
+
+        assert_eq!(config, expected());
+    }
+
+    #[test]
+    fn loads_from_json() {
+        let config: RetryPolicyConfig = serde_json::from_str(r#"{
+            "max_retries": 5,
+            "schedule": "linear",
+            "base": "100ms",
+            "max_delay": "90s",
+            "jitter": "full",
+            "max_elapsed": "5m"
+        }"#).unwrap();
+
+        assert_eq!(config, expected());
+    }
+
+    #[test]
+    fn loads_from_yaml() {
+        let config: RetryPolicyConfig = serde_yaml::from_str("
+            max_retries: 5
+            schedule: linear
+            base: 100ms
+            max_delay: 90s
+            jitter: full
+            max_elapsed: 5m
+        ").unwrap();
+
+        assert_eq!(config, expected());
+    }
+
+    #[test]
+    fn round_trips() {
+        let written = toml::to_string(&expected()).unwrap();
+        let config: RetryPolicyConfig = toml::from_str(&written).unwrap();
+
+        assert_eq!(config, expected());
+    }
+
+    #[test]
+    fn rejects_unknown_settings() {
+        let config = toml::from_str::<RetryPolicyConfig>("max_retires = 5");
+
+        assert!(config.is_err());
+    }
+
+    #[test]
+    fn builds_backoff() {
+        let config: RetryPolicyConfig = toml::from_str(r#"
+            max_retries = 4
+            base = "10ms"
+            exponent = 2.0
+            max_delay = "50ms"
+        "#).unwrap();
+        let backoff = config.build(|r: &Result<(), ()>| r.is_err()).unwrap();
+
+        let delays: Vec<_> = (1..5).map(|n| backoff.next_delay(n)).collect();
+        assert_eq!(delays, vec![
+            Some(Duration::from_millis(10)),
+            Some(Duration::from_millis(40)),
+            Some(Duration::from_millis(50)),
+            None
+        ]);
+    }
+
+    #[test]
+    fn builds_geometric_backoff() {
+        let config: RetryPolicyConfig = toml::from_str(r#"
+            max_retries = 5
+            schedule = "geometric"
+            base = "10ms"
+            multiplier = 3.0
+        "#).unwrap();
+        let backoff = config.build(|r: &Result<(), ()>| r.is_err()).unwrap();
+
+        let delays: Vec<_> = (1..6).map(|n| backoff.next_delay(n)).collect();
+        assert_eq!(delays, vec![
+            Some(Duration::from_millis(10)),
+            Some(Duration::from_millis(30)),
+            Some(Duration::from_millis(90)),
+            Some(Duration::from_millis(270)),
+            None
+        ]);
+    }
+
+    #[test]
+    fn refuses_invalid_configuration() {
+        let config = RetryPolicyConfig {
+            max_retries: 0,
+            ..RetryPolicyConfig::default()
+        };
+
+        assert_eq!(
+            config.build(|r: &Result<(), ()>| r.is_err()).err(),
+            Some(ConfigError::ZeroRetries)
+        );
+
+        let config = RetryPolicyConfig {
+            schedule: ScheduleKind::Geometric,
+            multiplier: 0.0,
+            ..RetryPolicyConfig::default()
+        };
+
+        assert_eq!(
+            config.build(|r: &Result<(), ()>| r.is_err()).err(),
+            Some(ConfigError::InvalidMultiplier(0.0))
+        );
+    }
+}
@@ -0,0 +1,89 @@
+use std::time::Duration;
+
+/// What an attempt made by `ExponentialBackoff::retry_with_context`
+/// knows about the retry it is part of.
+#[derive(Debug)]
+pub struct RetryContext<'a, E> {
+
+    /// The number of this attempt, starting from one.
+    pub attempt: u32,
+
+    /// The time since the first attempt started.
+    pub elapsed: Duration,
+
+    /// The time left before `max_elapsed` runs out, or `None`
+    /// when the backoff has no deadline.
+    pub remaining: Option<Duration>,
+
+    /// The error returned by the previous attempt, or `None` on
+    /// the first attempt and after an `Ok` which was retried.
+    pub previous_error: Option<&'a E>
+}
+
+impl <'a, E> RetryContext<'a, E> {
+
+    /// Whether this is the first attempt.
+    pub fn is_first_attempt(&self) -> bool {
+        self.attempt == 1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ExponentialBackoff, MockClock};
+    use std::time::Duration;
+
+    #[test]
+    fn tells_each_attempt_about_the_retry() {
+        let clock = MockClock::new();
+        let backoff = ExponentialBackoff::new(
+            4, Duration::ZERO, Duration::from_secs(1), 1.0,
+            |result: &Result<u32, String>| result.is_err()
+        ).with_clock(clock.clone()).with_sleeper(clock)
+            .with_max_elapsed(Duration::from_secs(10));
+        let mut seen = Vec::new();
+
+        let result = backoff.retry_with_context(|context| {
+            seen.push((
+                context.attempt,
+                context.elapsed,
+                context.remaining,
+                context.previous_error.cloned()
+            ));
+            if context.attempt < 3 {
+                Err(format!("attempt {} failed", context.attempt))
+            } else {
+                Ok(context.attempt)
+            }
+        });
+
+        assert_eq!(result, Ok(3));
+        assert_eq!(seen, vec![
+            (1, Duration::ZERO, Some(Duration::from_secs(10)), None),
+            (
+                2, Duration::from_secs(1), Some(Duration::from_secs(9)),
+                Some(String::from("attempt 1 failed"))
+            ),
+            (
+                3, Duration::from_secs(3), Some(Duration::from_secs(7)),
+                Some(String::from("attempt 2 failed"))
+            )
+        ]);
+    }
+
+    #[test]
+    fn has_no_deadline_without_max_elapsed() {
+        let backoff = ExponentialBackoff::new(
+            2, Duration::ZERO, Duration::from_secs(1), 1.0,
+            |result: &Result<(), ()>| result.is_err()
+        ).with_sleeper(MockClock::new());
+
+        let result = backoff.retry_with_context(|context| {
+            assert!(context.is_first_attempt());
+            assert_eq!(context.remaining, None);
+            Ok(())
+        });
+
+        assert_eq!(result, Ok(()));
+    }
+}
@@ -0,0 +1,32 @@
+use std::time::Duration;
+
+/// What to do with the result of an attempt, as decided by an
+/// `ExponentialBackoff`'s `should_retry` block.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Decision {
+
+    /// Retry after the time given by the backoff schedule.
+    Retry,
+
+    /// Retry after exactly this long, for instance because the
+    /// server asked for it with a `Retry-After` header. The
+    /// wait is neither jittered nor capped by `max_delay`, but
+    /// still counts towards the schedule's retries and
+    /// `max_elapsed`.
+    RetryAfter(Duration),
+
+    /// Hand the result back to the caller without retrying.
+    Stop
+}
+
+/// Lets plain `bool` predicates be used as `should_retry`
+/// blocks, `true` meaning `Decision::Retry`.
+impl From<bool> for Decision {
+    fn from(should_retry: bool) -> Decision {
+        if should_retry {
+            Decision::Retry
+        } else {
+            Decision::Stop
+        }
+    }
+}
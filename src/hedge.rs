@@ -0,0 +1,251 @@
+use std::collections::VecDeque;
+use std::future::{poll_fn, Future};
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::task::Poll;
+use std::time::{Duration, Instant};
+
+use crate::{AsyncSleeper, Clock, SystemClock};
+
+/// How long a `Hedge` waits for an attempt before starting
+/// another alongside it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HedgeDelay {
+
+    /// Always wait this long.
+    Fixed(Duration),
+
+    /// Wait as long as the given percentile, between 0 and 1,
+    /// of the latencies of the last `window` successful calls,
+    /// so that only the slowest calls are hedged. Until any
+    /// latencies have been seen, wait `fallback`.
+    Percentile {
+
+        /// The percentile of recent latencies to wait.
+        percentile: f64,
+
+        /// The number of recent latencies to remember.
+        window: usize,
+
+        /// The wait used before any latencies are known.
+        fallback: Duration
+    }
+}
+
+/// Runs speculative, parallel attempts of a slow operation:
+/// rather than waiting for an attempt to fail, another is
+/// started whenever the hedge delay passes without an answer,
+/// up to `max_attempts` in flight in total. The first success
+/// wins, and the attempts still running are dropped, which
+/// cancels them. An attempt which fails starts the next one
+/// straight away, and if every attempt fails the last error is
+/// returned.
+///
+/// Hedging is only appropriate for operations which are safe
+/// to run more than once at the same time, such as reads.
+pub struct Hedge {
+    delay: HedgeDelay,
+    max_attempts: u32,
+    clock: Box<dyn Clock + Send + Sync>,
+    latencies: Mutex<VecDeque<Duration>>
+}
+
+impl Hedge {
+
+    /// Creates a hedge which starts another attempt every
+    /// `hedge_delay`, making at most `max_attempts` in total.
+    pub fn new(hedge_delay: Duration, max_attempts: u32) -> Hedge {
+        Hedge::with_delay(HedgeDelay::Fixed(hedge_delay), max_attempts)
+    }
+
+    /// Creates a hedge which waits `delay` before starting each
+    /// additional attempt, making at most `max_attempts` in
+    /// total.
+    pub fn with_delay(delay: HedgeDelay, max_attempts: u32) -> Hedge {
+        Hedge {
+            delay,
+            max_attempts,
+            clock: Box::new(SystemClock),
+            latencies: Mutex::new(VecDeque::new())
+        }
+    }
+
+    /// Reads the time, for measuring latencies, from `clock`
+    /// instead of the system clock.
+    pub fn with_clock<
+        TClock: Clock + Send + Sync + 'static
+    > (mut self, clock: TClock) -> Hedge {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// Returns how long the hedge will currently wait before
+    /// starting another attempt.
+    pub fn hedge_delay(&self) -> Duration {
+        let (percentile, fallback) = match self.delay {
+            HedgeDelay::Fixed(delay) => return delay,
+            HedgeDelay::Percentile { percentile, fallback, .. } => {
+                (percentile, fallback)
+            }
+        };
+
+        let mut latencies: Vec<Duration> = self.latencies.lock().unwrap()
+            .iter().copied().collect();
+        if latencies.is_empty() {
+            return fallback;
+        }
+
+        latencies.sort();
+        let rank = (percentile.clamp(0.0, 1.0) * latencies.len() as f64).ceil();
+        latencies[(rank as usize).clamp(1, latencies.len()) - 1]
+    }
+
+    /// Runs `retriable_block`, starting additional attempts
+    /// whenever the hedge delay passes without an answer, as
+    /// timed by `sleeper`.
+    pub async fn run<T, E, TSleeper, TRetriable, TFuture>(
+        &self,
+        sleeper: &TSleeper,
+        mut retriable_block: TRetriable
+    ) -> Result<T, E> where
+        TSleeper: AsyncSleeper + ?Sized,
+        TRetriable: FnMut() -> TFuture,
+        TFuture: Future<Output = Result<T, E>>
+    {
+        let mut in_flight: Vec<(Instant, Pin<Box<TFuture>>)> = Vec::new();
+        let mut started = 0;
+        let mut hedge_timer: Option<Pin<Box<TSleeper::Sleep>>> = None;
+        let mut last_error = None;
+
+        poll_fn(|cx| loop {
+            let mut start_another = started == 0;
+
+            let mut index = 0;
+            while index < in_flight.len() {
+                let (started_at, attempt) = &mut in_flight[index];
+                match attempt.as_mut().poll(cx) {
+                    Poll::Ready(Ok(value)) => {
+                        self.record_latency(*started_at);
+                        return Poll::Ready(Ok(value));
+                    },
+                    Poll::Ready(Err(error)) => {
+                        last_error = Some(error);
+                        in_flight.swap_remove(index);
+                        start_another = true;
+                    },
+                    Poll::Pending => index += 1
+                }
+            }
+
+            if let Some(timer) = &mut hedge_timer {
+                if timer.as_mut().poll(cx).is_ready() {
+                    hedge_timer = None;
+                    start_another = true;
+                }
+            }
+
+            if start_another && started < self.max_attempts.max(1) {
+                started += 1;
+                in_flight.push((self.clock.now(), Box::pin(retriable_block())));
+                hedge_timer = if started < self.max_attempts {
+                    Some(Box::pin(sleeper.sleep(self.hedge_delay())))
+                } else {
+                    None
+                };
+                continue;
+            }
+
+            if in_flight.is_empty() {
+                if let Some(error) = last_error.take() {
+                    return Poll::Ready(Err(error));
+                }
+            }
+
+            return Poll::Pending;
+        }).await
+    }
+
+    fn record_latency(&self, started_at: Instant) {
+        let window = match self.delay {
+            HedgeDelay::Fixed(_) => return,
+            HedgeDelay::Percentile { window, .. } => window
+        };
+
+        let latency = self.clock.now().saturating_duration_since(started_at);
+        let mut latencies = self.latencies.lock().unwrap();
+        latencies.push_back(latency);
+        while latencies.len() > window {
+            latencies.pop_front();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Hedge, HedgeDelay, MockClock};
+    use std::future::{pending, ready, Future};
+    use std::pin::Pin;
+    use std::time::Duration;
+
+    type Attempt = Pin<Box<dyn Future<Output = Result<u32, u32>>>>;
+
+    #[tokio::test]
+    async fn first_success_wins_over_stragglers() {
+        let clock = MockClock::new();
+        let hedge = Hedge::new(Duration::from_millis(50), 3);
+        let mut started = 0;
+
+        let result = hedge.run(&clock, || -> Attempt {
+            started += 1;
+            match started {
+                // the first attempt hangs, and is left behind
+                1 => Box::pin(pending()),
+                attempt => Box::pin(ready(Ok(attempt)))
+            }
+        }).await;
+
+        assert_eq!(result, Ok(2));
+        assert_eq!(started, 2);
+        assert_eq!(clock.sleeps(), vec![Duration::from_millis(50); 2]);
+    }
+
+    #[tokio::test]
+    async fn returns_last_error_when_every_attempt_fails() {
+        let clock = MockClock::new();
+        let hedge = Hedge::new(Duration::from_secs(1), 3);
+        let mut started = 0;
+
+        let result = hedge.run(&clock, || -> Attempt {
+            started += 1;
+            Box::pin(ready(Err(started)))
+        }).await;
+
+        assert_eq!(result, Err(3));
+        assert_eq!(started, 3);
+    }
+
+    #[tokio::test]
+    async fn hedges_at_latency_percentile() {
+        let clock = MockClock::new();
+        let sleeper = MockClock::new();
+        let hedge = Hedge::with_delay(HedgeDelay::Percentile {
+            percentile: 0.9,
+            window: 10,
+            fallback: Duration::from_millis(250)
+        }, 2).with_clock(clock.clone());
+
+        assert_eq!(hedge.hedge_delay(), Duration::from_millis(250));
+
+        for millis in 1..=20 {
+            let latency = Duration::from_millis(millis * 10);
+            let result = hedge.run(&sleeper, || {
+                clock.advance(latency);
+                ready(Ok::<(), ()>(()))
+            }).await;
+            assert!(result.is_ok());
+        }
+
+        // the window holds 110ms through 200ms
+        assert_eq!(hedge.hedge_delay(), Duration::from_millis(190));
+    }
+}
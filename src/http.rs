@@ -0,0 +1,144 @@
+//! Retry decisions for HTTP responses, over the `http` crate's
+//! `StatusCode` and `HeaderMap`. Enabled by the `http` feature.
+//!
+//! Requests which timed out (408), were throttled (429) or hit
+//! a server error (5xx) are retried, and every other status is
+//! not. When the server says how long to wait with a
+//! `Retry-After` header, in seconds or as an HTTP date, that
+//! wait replaces the backoff schedule's.
+
+use std::time::{Duration, SystemTime};
+
+use ::http::header::RETRY_AFTER;
+use ::http::{HeaderMap, Response, StatusCode};
+
+use crate::Decision;
+
+/// Whether a response with `status` is worth retrying.
+pub fn is_retriable_status(status: StatusCode) -> bool {
+    status == StatusCode::REQUEST_TIMEOUT
+        || status == StatusCode::TOO_MANY_REQUESTS
+        || status.is_server_error()
+}
+
+/// Decides how to handle a response with `status` and
+/// `headers`, honouring any `Retry-After` header on a
+/// retriable response.
+pub fn classify(status: StatusCode, headers: &HeaderMap) -> Decision {
+    classify_at(status, headers, SystemTime::now())
+}
+
+/// Decides how to handle `response`, like `classify`.
+pub fn classify_response<B>(response: &Response<B>) -> Decision {
+    classify(response.status(), response.headers())
+}
+
+/// Decides how to handle a response like `classify`, measuring
+/// any `Retry-After` date from `now` rather than the system
+/// clock.
+pub fn classify_at(
+    status: StatusCode,
+    headers: &HeaderMap,
+    now: SystemTime
+) -> Decision {
+    if !is_retriable_status(status) {
+        return Decision::Stop;
+    }
+
+    match retry_after_at(headers, now) {
+        Some(retry_after) => Decision::RetryAfter(retry_after),
+        None => Decision::Retry
+    }
+}
+
+/// Reads the wait requested by a `Retry-After` header, which
+/// may be a number of seconds or an HTTP date. Dates in the
+/// past ask for no wait at all. Returns `None` when there is
+/// no such header or it cannot be understood.
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    retry_after_at(headers, SystemTime::now())
+}
+
+/// Reads the wait requested by a `Retry-After` header like
+/// `retry_after`, measuring dates from `now`.
+pub fn retry_after_at(headers: &HeaderMap, now: SystemTime) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(date.duration_since(now).unwrap_or(Duration::ZERO))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::http::{classify_at, classify_response, retry_after_at};
+    use crate::Decision;
+    use ::http::{HeaderMap, HeaderValue, Response, StatusCode};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    /// Wed, 21 Oct 2015 07:28:00 GMT
+    fn recorded_at() -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(1_445_412_480)
+    }
+
+    fn headers(lines: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in lines {
+            headers.insert(*name, HeaderValue::from_static(value));
+        }
+        headers
+    }
+
+    #[test]
+    fn classifies_recorded_responses() {
+        let recorded: Vec<(u16, HeaderMap, Decision)> = vec![
+            (200, headers(&[("content-type", "application/json")]), Decision::Stop),
+            (404, headers(&[("content-length", "0")]), Decision::Stop),
+            (400, headers(&[("retry-after", "5")]), Decision::Stop),
+            (408, headers(&[("connection", "close")]), Decision::Retry),
+            (
+                429,
+                headers(&[("retry-after", "120"), ("x-ratelimit-remaining", "0")]),
+                Decision::RetryAfter(Duration::from_secs(120))
+            ),
+            (
+                503,
+                headers(&[("retry-after", "Wed, 21 Oct 2015 07:28:30 GMT")]),
+                Decision::RetryAfter(Duration::from_secs(30))
+            ),
+            (
+                503,
+                headers(&[("retry-after", "Wednesday, 21-Oct-15 07:27:00 GMT")]),
+                Decision::RetryAfter(Duration::ZERO)
+            ),
+            (502, headers(&[("retry-after", "soon")]), Decision::Retry),
+            (500, HeaderMap::new(), Decision::Retry)
+        ];
+
+        for (status, headers, decision) in recorded {
+            let status = StatusCode::from_u16(status).unwrap();
+            assert_eq!(classify_at(status, &headers, recorded_at()), decision);
+        }
+    }
+
+    #[test]
+    fn reads_retry_after_from_responses() {
+        let response = Response::builder()
+            .status(429)
+            .header("Retry-After", " 7 ")
+            .body(())
+            .unwrap();
+
+        assert_eq!(
+            retry_after_at(response.headers(), recorded_at()),
+            Some(Duration::from_secs(7))
+        );
+        assert_eq!(
+            classify_response(&response),
+            Decision::RetryAfter(Duration::from_secs(7))
+        );
+    }
+}
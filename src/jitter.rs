@@ -0,0 +1,188 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+/// How much randomness to mix into each backoff time, so that
+/// clients which fail together do not all retry together. The
+/// strategies follow the AWS architecture blog's
+/// [Exponential Backoff And Jitter](https://aws.amazon.com/blogs/architecture/exponential-backoff-and-jitter/).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
+pub enum Jitter {
+
+    /// Wait exactly the computed backoff time.
+    #[default]
+    None,
+
+    /// Wait a random time between zero and the computed
+    /// backoff time.
+    Full,
+
+    /// Wait half of the computed backoff time, plus a random
+    /// time between zero and the other half.
+    Equal,
+
+    /// Wait a random time between the first backoff time and
+    /// three times the previous wait, ignoring the computed
+    /// backoff time after the first attempt. The range is cut
+    /// off at `max_delay`, so waits stay spread out below the
+    /// cap rather than piling up at it.
+    Decorrelated
+}
+
+/// The per-call state needed to apply a `Jitter`: the random
+/// number generator, and the waits it has already produced.
+pub(crate) struct JitterState {
+    jitter: Jitter,
+    rng: SplitMix64,
+    base: Option<Duration>,
+    previous: Option<Duration>
+}
+
+impl JitterState {
+
+    /// Starts a new sequence of waits, seeded with `seed` when
+    /// one is given or with a fresh random seed otherwise.
+    pub(crate) fn new(jitter: Jitter, seed: Option<u64>) -> JitterState {
+        let seed = seed.unwrap_or_else(|| {
+            RandomState::new().build_hasher().finish()
+        });
+
+        JitterState {
+            jitter,
+            rng: SplitMix64(seed),
+            base: None,
+            previous: None
+        }
+    }
+
+    /// Randomizes the computed backoff time `delay` for the
+    /// next wait, which is never longer than `max_delay`.
+    pub(crate) fn apply(
+        &mut self,
+        delay: Duration,
+        max_delay: Option<Duration>
+    ) -> Duration {
+        let max_delay = max_delay.unwrap_or(Duration::MAX);
+        let delay = delay.min(max_delay);
+        let jittered = match self.jitter {
+            Jitter::None => delay,
+            Jitter::Full => self.rng.between(Duration::ZERO, delay),
+            Jitter::Equal => {
+                let half = delay / 2;
+                half + self.rng.between(Duration::ZERO, delay - half)
+            },
+            Jitter::Decorrelated => {
+                let base = *self.base.get_or_insert(delay);
+                let previous = self.previous.unwrap_or(base);
+                let ceiling = previous.checked_mul(3)
+                    .unwrap_or(Duration::MAX)
+                    .min(max_delay)
+                    .max(base);
+                self.rng.between(base, ceiling)
+            }
+        };
+
+        self.previous = Some(jittered);
+        jittered
+    }
+}
+
+/// A small, fast, seedable generator; see
+/// <https://prng.di.unimi.it/splitmix64.c>. It is nowhere near
+/// cryptographically secure, which is fine for spreading out
+/// retries.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Picks a duration between `low` and `high`, inclusive, to
+    /// nanosecond precision.
+    fn between(&mut self, low: Duration, high: Duration) -> Duration {
+        let span = (high - low).as_nanos().min(u64::MAX as u128 - 1) as u64;
+        let offset = (self.next_u64() as u128 * (span as u128 + 1)) >> 64;
+        low + Duration::from_nanos(offset as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::jitter::{Jitter, JitterState};
+    use std::time::Duration;
+
+    #[test]
+    fn same_seed_same_waits() {
+        for jitter in [Jitter::Full, Jitter::Equal, Jitter::Decorrelated] {
+            assert_eq!(waits(jitter, 42), waits(jitter, 42));
+            assert_ne!(waits(jitter, 42), waits(jitter, 43));
+        }
+    }
+
+    #[test]
+    fn waits_stay_within_bounds() {
+        let delays = delays();
+
+        for seed in 0..100 {
+            let full = waits(Jitter::Full, seed);
+            let equal = waits(Jitter::Equal, seed);
+            let decorrelated = waits(Jitter::Decorrelated, seed);
+
+            for i in 0..delays.len() {
+                assert!(full[i] <= delays[i]);
+                assert!(equal[i] >= delays[i] / 2);
+                assert!(equal[i] <= delays[i]);
+                assert!(decorrelated[i] >= delays[0]);
+            }
+
+            for i in 1..delays.len() {
+                assert!(decorrelated[i] <= decorrelated[i - 1] * 3);
+            }
+        }
+    }
+
+    #[test]
+    fn decorrelated_waits_spread_out_under_a_cap() {
+        let base = Duration::from_millis(100);
+        let cap = Duration::from_secs(1);
+
+        for seed in 0..20 {
+            let mut state = JitterState::new(Jitter::Decorrelated, Some(seed));
+            let waits: Vec<Duration> = (0..60)
+                .map(|_| state.apply(base, Some(cap)))
+                .collect();
+
+            assert!(waits.iter().all(|wait| *wait >= base && *wait <= cap));
+            assert!(waits.iter().filter(|wait| **wait == cap).count() <= 1);
+
+            let tail_below_half = waits[30..].iter()
+                .filter(|wait| **wait < cap / 2)
+                .count();
+            assert!(tail_below_half > 0);
+        }
+    }
+
+    #[test]
+    fn no_jitter_is_exact() {
+        assert_eq!(waits(Jitter::None, 7), delays());
+    }
+
+    fn delays() -> Vec<Duration> {
+        (1..=5u64).map(|n| Duration::from_millis(100 * n * n)).collect()
+    }
+
+    fn waits(jitter: Jitter, seed: u64) -> Vec<Duration> {
+        let mut state = JitterState::new(jitter, Some(seed));
+        delays().into_iter().map(|delay| state.apply(delay, None)).collect()
+    }
+}
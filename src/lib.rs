@@ -1,91 +1,883 @@
-use std::time::Duration;
-use std::thread::sleep;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-/// An exponential backoff, measured in milliseconds, which
-/// retries until it reaches `max_retries`. As an exponential
+use crate::jitter::JitterState;
+
+mod backoff;
+mod breaker;
+mod budget;
+mod builder;
+mod cancel;
+mod clock;
+mod context;
+#[cfg(feature = "serde")]
+mod config;
+mod decision;
+mod hedge;
+#[cfg(feature = "http")]
+pub mod http;
+mod jitter;
+#[cfg(feature = "metrics")]
+mod meter;
+mod poll;
+mod report;
+mod retryable;
+mod sleep;
+#[cfg(feature = "tower")]
+pub mod tower;
+#[cfg(feature = "tracing")]
+mod trace;
+
+pub use crate::backoff::{
+    Backoff, ConstantBackoff, FibonacciBackoff, GeometricBackoff,
+    LinearBackoff, ListBackoff
+};
+pub use crate::breaker::{
+    CircuitBreaker, CircuitError, CircuitState, Threshold
+};
+pub use crate::budget::RetryBudget;
+pub use crate::builder::{ConfigError, ExponentialBackoffBuilder};
+pub use crate::cancel::{CancelError, CancellationToken};
+pub use crate::clock::{Clock, MockClock, SystemClock};
+pub use crate::context::RetryContext;
+#[cfg(feature = "serde")]
+pub use crate::config::{RetryPolicyConfig, ScheduleKind};
+pub use crate::decision::Decision;
+pub use crate::hedge::{Hedge, HedgeDelay};
+pub use crate::jitter::Jitter;
+pub use crate::poll::PollError;
+pub use crate::report::{RetryError, RetryReport};
+pub use crate::retryable::{is_transient_error, retry_transient, Retryable};
+#[cfg(feature = "macros")]
+pub use mysteriouspants_retry_macros::{retry, Retryable};
+pub use crate::sleep::{AsyncSleeper, Sleeper, ThreadSleeper};
+#[cfg(feature = "tokio")]
+pub use crate::sleep::TokioSleeper;
+#[cfg(feature = "async-std")]
+pub use crate::sleep::AsyncStdSleeper;
+
+/// Implementation details of the procedural macros. Not public
+/// API.
+#[doc(hidden)]
+pub mod __private {
+    pub use crate::retryable::RetryAfterValue;
+
+    use std::future::Future;
+    use std::ops::ControlFlow;
+    use std::time::Duration;
+
+    use crate::{Attempts, ExponentialBackoff, ExponentialBackoffBuilder, Next};
+
+    /// The `Ok` and `Err` types of a `Result`, which lets the
+    /// `#[retry]` attribute name them from a function's return
+    /// type.
+    pub trait ResultType {
+        type Ok;
+        type Err;
+    }
+
+    impl <T, E> ResultType for Result<T, E> {
+        type Ok = T;
+        type Err = E;
+    }
+
+    /// Starts a builder for a backoff retrying a function which
+    /// returns `R`.
+    pub fn builder_for<R: ResultType>() -> ExponentialBackoffBuilder<R::Ok, R::Err> {
+        ExponentialBackoff::builder()
+    }
+
+    /// Builds the backoff configured by a `#[retry]` attribute,
+    /// whose settings were checked when it expanded.
+    pub fn prechecked<T, E>(
+        builder: ExponentialBackoffBuilder<T, E>
+    ) -> ExponentialBackoff<T, E> {
+        builder.build_unchecked()
+    }
+
+    /// Steps through the retry loop a `#[retry]` attribute
+    /// writes out, so that each attempt can run the function's
+    /// body in place, borrowing its arguments.
+    pub struct Retrying<'a, T, E>(Attempts<'a, T, E>);
+
+    /// Starts retrying with `backoff`.
+    pub fn retrying<T, E>(backoff: &ExponentialBackoff<T, E>) -> Retrying<'_, T, E> {
+        Retrying(Attempts::new(backoff))
+    }
+
+    impl <'a, T, E> Retrying<'a, T, E> {
+
+        /// Makes a blocking attempt.
+        pub fn attempt<R>(&self, retriable_block: impl FnOnce() -> R) -> R {
+            self.0.attempt(retriable_block)
+        }
+
+        /// Prepares an asynchronous attempt.
+        pub fn instrument<F: Future>(&self, attempt: F) -> impl Future<Output = F::Output> {
+            self.0.instrument(attempt)
+        }
+
+        /// Decides whether to wait and try again after `result`,
+        /// or to return it.
+        pub fn next(&mut self, result: Result<T, E>) -> ControlFlow<Result<T, E>, Duration> {
+            match self.0.next(result) {
+                Next::Wait(backoff_time) => ControlFlow::Continue(backoff_time),
+                Next::Finish(report) => ControlFlow::Break(report.result)
+            }
+        }
+
+        /// Waits with the backoff's own sleeper.
+        pub fn sleep(&self, backoff_time: Duration) {
+            self.0.backoff.sleeper.sleep(backoff_time);
+        }
+    }
+}
+
+/// Block deciding whether a given `Result` ought to be
+/// retried, and how soon.
+pub type ShouldRetry<T, E> = dyn Fn(&Result<T, E>) -> Decision + Send + Sync;
+
+/// Hook called with the attempt number, its result and the
+/// time about to be waited, whenever an attempt is going to be
+/// retried.
+pub type OnRetry<T, E> = dyn Fn(u32, &Result<T, E>, Duration) + Send + Sync;
+
+/// Hook called with the number of attempts made and the error
+/// returned when retrying stops with an error.
+pub type OnGiveUp<E> = dyn Fn(u32, &E) + Send + Sync;
+
+/// Hook called with the number of attempts made and the value
+/// returned when retrying stops with a success.
+pub type OnSuccess<T> = dyn Fn(u32, &T) + Send + Sync;
+
+/// Block turning an error into the message recorded in traces.
+pub type ErrorMessage<E> = dyn Fn(&E) -> String + Send + Sync;
+
+/// An exponential backoff which retries until it reaches
+/// `max_retries`. As an exponential
 /// backoff, it follows the formula *an^b+c*, where *a* is
 /// `coefficient`, *b* is `exponent`, *c* is `constant`, and
 /// *n* is the attempt number. This means that the total time
 /// possible to spend waiting in a retry is given by the sum,
 /// from *1* to `max_retries`, of *an^b+c*.
+///
+/// The exponential formula can be swapped for any other
+/// `Backoff` schedule with `with_schedule`, keeping the rest of
+/// the configuration, and `from_schedule` starts a backoff from
+/// a schedule alone.
 pub struct ExponentialBackoff<T, E> {
 
-    /// Block describing whether a given `Result` ought to be
-    /// considered retriable.
-    pub should_retry: Box<Fn(&Result<T, E>) -> bool + Send + Sync>,
+    /// Block deciding whether a given `Result` ought to be
+    /// retried, and how soon.
+    pub should_retry: Box<ShouldRetry<T, E>>,
 
     /// The maximum number of times to retry the operation
     /// before giving up.
     pub max_retries: u8,
 
     /// The constant to add to each backoff time.
-    pub constant: f32,
+    pub constant: Duration,
 
     /// The coefficient to multiply each exponentiated backoff
     /// time by, before adding `constant`.
-    pub coefficient: f32,
+    pub coefficient: Duration,
 
     /// The exponent to raise the retry attempt to.
-    pub exponent: f32
+    pub exponent: f32,
+
+    /// The randomness to mix into each backoff time.
+    pub jitter: Jitter,
+
+    /// Seeds the random number generator used for `jitter`, so
+    /// that every retry waits the same sequence of times. When
+    /// `None`, each retry draws a fresh random seed.
+    pub jitter_seed: Option<u64>,
+
+    /// A schedule to follow instead of the exponential
+    /// formula. When set, `max_retries`, `constant`,
+    /// `coefficient` and `exponent` are ignored.
+    pub schedule: Option<Box<dyn Backoff + Send + Sync>>,
+
+    /// The longest time to wait before any single retry,
+    /// however large the schedule and jitter make it.
+    pub max_delay: Option<Duration>,
+
+    /// The longest time to spend retrying, measured from the
+    /// start of the first attempt. Once waiting for another
+    /// attempt would run past it, the last result is returned
+    /// immediately rather than sleeping.
+    pub max_elapsed: Option<Duration>,
+
+    /// Called before waiting to retry a failed attempt.
+    pub on_retry: Option<Box<OnRetry<T, E>>>,
+
+    /// Called when retrying stops with an error, whether
+    /// because it was not retriable or because the retries
+    /// ran out.
+    pub on_give_up: Option<Box<OnGiveUp<E>>>,
+
+    /// Called when retrying stops with a success.
+    pub on_success: Option<Box<OnSuccess<T>>>,
+
+    /// The clock used to measure `max_elapsed`.
+    pub clock: Box<dyn Clock + Send + Sync>,
+
+    /// Waits between the attempts of `retry`, and the other
+    /// blocking retry loops.
+    pub sleeper: Box<dyn Sleeper + Send + Sync>,
+
+    /// A budget, possibly shared with other backoffs, which
+    /// every retry must be paid for from.
+    pub retry_budget: Option<Arc<RetryBudget>>,
+
+    /// The name of the operation being retried, which labels
+    /// its metrics and traces.
+    pub operation: Option<String>,
+
+    /// Turns errors into the messages recorded in traces. When
+    /// `None`, traces record only the type of each error.
+    pub error_message: Option<Box<ErrorMessage<E>>>
 }
 
 impl <T, E> ExponentialBackoff<T, E> {
     
     /// A default backoff configured for networking with a
     /// [61-second total backoff time](https://www.wolframalpha.com/input/?i=sum+0%2B1000t%5E1.5+from+1+to+7).
+    ///
+    /// `should_retry` may return either a `bool` or a
+    /// `Decision`.
     pub fn new_with_defaults<
-        TShouldRetry: Fn(&Result<T, E>) -> bool + Send + Sync + 'static
+        TDecision: Into<Decision>,
+        TShouldRetry: Fn(&Result<T, E>) -> TDecision + Send + Sync + 'static
     > (should_retry: TShouldRetry) -> ExponentialBackoff<T, E> {
         // https://www.wolframalpha.com/input/?i=sum+0%2B1000t%5E1.5+from+1+to+7
-        return ExponentialBackoff::new(7, 0.0, 1000.0, 0.5, should_retry);
+        ExponentialBackoff::new(
+            7, Duration::ZERO, Duration::from_secs(1), 0.5, should_retry
+        )
+    }
+
+    /// A default backoff, like `new_with_defaults`, which
+    /// retries the errors that their `Retryable` implementation
+    /// considers transient.
+    pub fn for_transient_errors() -> ExponentialBackoff<T, E> where E: Retryable {
+        ExponentialBackoff::new_with_defaults(
+            |result: &Result<T, E>| retry_transient(result)
+        )
+    }
+
+    /// Creates a backoff which follows `schedule`, with none of
+    /// the exponential formula's settings, retrying results as
+    /// `should_retry` decides. The number of attempts is up to
+    /// the schedule.
+    pub fn from_schedule<
+        TBackoff: Backoff + Send + Sync + 'static,
+        TDecision: Into<Decision>,
+        TShouldRetry: Fn(&Result<T, E>) -> TDecision + Send + Sync + 'static
+    > (schedule: TBackoff, should_retry: TShouldRetry) -> ExponentialBackoff<T, E> {
+        ExponentialBackoff::new_with_defaults(should_retry).with_schedule(schedule)
     }
 
-    /// Creates a new backoff.
+    /// Starts building a backoff from named settings, which
+    /// are checked before the backoff is built.
+    pub fn builder() -> ExponentialBackoffBuilder<T, E> {
+        ExponentialBackoffBuilder::new()
+    }
+
+    /// Creates a new backoff. `should_retry` may return either
+    /// a `bool` or a `Decision`.
     pub fn new<
-        TShouldRetry: Fn(&Result<T, E>) -> bool + Send + Sync + 'static
+        TDecision: Into<Decision>,
+        TShouldRetry: Fn(&Result<T, E>) -> TDecision + Send + Sync + 'static
     > (
         max_retries: u8,
-        constant: f32,
-        coefficient: f32,
+        constant: Duration,
+        coefficient: Duration,
         exponent: f32,
         should_retry: TShouldRetry
     ) -> ExponentialBackoff<T, E> {
-        return ExponentialBackoff {
-            should_retry: Box::new(should_retry),
-            max_retries: max_retries,
-            constant: constant,
-            coefficient: coefficient,
-            exponent: exponent
-        };
+        ExponentialBackoff {
+            should_retry: Box::new(move |result| should_retry(result).into()),
+            max_retries,
+            constant,
+            coefficient,
+            exponent,
+            jitter: Jitter::None,
+            jitter_seed: None,
+            schedule: None,
+            max_delay: None,
+            max_elapsed: None,
+            on_retry: None,
+            on_give_up: None,
+            on_success: None,
+            clock: Box::new(SystemClock),
+            sleeper: Box::new(ThreadSleeper),
+            retry_budget: None,
+            operation: None,
+            error_message: None
+        }
+    }
+
+    /// Follows `schedule` instead of the exponential formula.
+    pub fn with_schedule<
+        TBackoff: Backoff + Send + Sync + 'static
+    > (mut self, schedule: TBackoff) -> ExponentialBackoff<T, E> {
+        self.schedule = Some(Box::new(schedule));
+        self
+    }
+
+    /// Mixes `jitter` into each backoff time.
+    pub fn with_jitter(mut self, jitter: Jitter) -> ExponentialBackoff<T, E> {
+        self.jitter = jitter;
+        self
+    }
+
+    /// Seeds the jitter's random number generator, making the
+    /// backoff times of every retry repeatable.
+    pub fn with_jitter_seed(mut self, seed: u64) -> ExponentialBackoff<T, E> {
+        self.jitter_seed = Some(seed);
+        self
+    }
+
+    /// Never waits longer than `max_delay` before a retry.
+    pub fn with_max_delay(mut self, max_delay: Duration) -> ExponentialBackoff<T, E> {
+        self.max_delay = Some(max_delay);
+        self
+    }
+
+    /// Gives up once `max_elapsed` would be exceeded by
+    /// waiting for another attempt.
+    pub fn with_max_elapsed(mut self, max_elapsed: Duration) -> ExponentialBackoff<T, E> {
+        self.max_elapsed = Some(max_elapsed);
+        self
+    }
+
+    /// Calls `on_retry` before waiting to retry a failed
+    /// attempt.
+    pub fn with_on_retry<
+        TOnRetry: Fn(u32, &Result<T, E>, Duration) + Send + Sync + 'static
+    > (mut self, on_retry: TOnRetry) -> ExponentialBackoff<T, E> {
+        self.on_retry = Some(Box::new(on_retry));
+        self
+    }
+
+    /// Calls `on_give_up` when retrying stops with an error.
+    pub fn with_on_give_up<
+        TOnGiveUp: Fn(u32, &E) + Send + Sync + 'static
+    > (mut self, on_give_up: TOnGiveUp) -> ExponentialBackoff<T, E> {
+        self.on_give_up = Some(Box::new(on_give_up));
+        self
+    }
+
+    /// Calls `on_success` when retrying stops with a success.
+    pub fn with_on_success<
+        TOnSuccess: Fn(u32, &T) + Send + Sync + 'static
+    > (mut self, on_success: TOnSuccess) -> ExponentialBackoff<T, E> {
+        self.on_success = Some(Box::new(on_success));
+        self
+    }
+
+    /// Reads the time from `clock` instead of the system clock.
+    pub fn with_clock<
+        TClock: Clock + Send + Sync + 'static
+    > (mut self, clock: TClock) -> ExponentialBackoff<T, E> {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// Waits between blocking attempts with `sleeper` instead
+    /// of `std::thread::sleep`.
+    pub fn with_sleeper<
+        TSleeper: Sleeper + Send + Sync + 'static
+    > (mut self, sleeper: TSleeper) -> ExponentialBackoff<T, E> {
+        self.sleeper = Box::new(sleeper);
+        self
+    }
+
+    /// Pays for every retry from `retry_budget`, giving up once
+    /// it is spent.
+    pub fn with_retry_budget(
+        mut self,
+        retry_budget: Arc<RetryBudget>
+    ) -> ExponentialBackoff<T, E> {
+        self.retry_budget = Some(retry_budget);
+        self
+    }
+
+    /// Names the operation being retried, to label its metrics
+    /// and traces.
+    pub fn with_operation<
+        TOperation: Into<String>
+    > (mut self, operation: TOperation) -> ExponentialBackoff<T, E> {
+        self.operation = Some(operation.into());
+        self
+    }
+
+    /// Records the message of each error in traces, as well as
+    /// its type, when the `tracing` feature is enabled. Left off
+    /// by default, since error messages may hold details which
+    /// ought not to be logged.
+    pub fn with_error_messages(mut self) -> ExponentialBackoff<T, E> where
+        E: std::fmt::Display
+    {
+        self.error_message = Some(Box::new(|error: &E| error.to_string()));
+        self
     }
 
     /// Executes an operation, retrying it until it succeeds
     /// or the maximum number of retries has been exhausted.
     pub fn retry<TRetriable>(
         &self,
-        mut retriable_block: TRetriable
+        retriable_block: TRetriable
     ) -> Result<T, E> where TRetriable : FnMut() -> Result<T, E> {
-        let mut retry_count: u8 = 0;
+        self.retry_with_report(retriable_block).result
+    }
+
+    /// Executes an operation like `retry`, but reports on every
+    /// attempt made rather than just the final result.
+    pub fn retry_with_report<TRetriable>(
+        &self,
+        mut retriable_block: TRetriable
+    ) -> RetryReport<T, E> where TRetriable : FnMut() -> Result<T, E> {
+        let mut attempts = Attempts::new(self);
 
         loop {
-            retry_count += 1;
-            let result = retriable_block();
-
-            if retry_count == self.max_retries
-                || !(self.should_retry)(&result) {
-                return result;
-            } else {
-                let backoff_time = self.constant + self.coefficient
-                    * (retry_count as f32).powf(self.exponent);
-                sleep(Duration::from_millis(backoff_time as u64));
+            match attempts.next(attempts.attempt(&mut retriable_block)) {
+                Next::Wait(backoff_time) => self.sleeper.sleep(backoff_time),
+                Next::Finish(report) => return report
+            }
+        }
+    }
+
+    /// Executes an operation like `retry`, but tells each
+    /// attempt which attempt it is, how long has elapsed, how
+    /// long is left before `max_elapsed`, and what the previous
+    /// attempt's error was.
+    pub fn retry_with_context<TRetriable>(
+        &self,
+        mut retriable_block: TRetriable
+    ) -> Result<T, E> where
+        TRetriable: FnMut(&RetryContext<'_, E>) -> Result<T, E>
+    {
+        let mut attempts = Attempts::new(self);
+
+        loop {
+            let result = attempts.attempt(|| retriable_block(&attempts.context()));
+            match attempts.next(result) {
+                Next::Wait(backoff_time) => self.sleeper.sleep(backoff_time),
+                Next::Finish(report) => return report.result
+            }
+        }
+    }
+
+    /// Executes an operation like `retry`, but stops as soon as
+    /// `token` is cancelled: no attempt is started after
+    /// cancellation, and a retry sleeping between attempts is
+    /// woken immediately.
+    pub fn retry_cancellable<TRetriable>(
+        &self,
+        token: &CancellationToken,
+        mut retriable_block: TRetriable
+    ) -> Result<T, CancelError<E>> where TRetriable : FnMut() -> Result<T, E> {
+        let mut attempts = Attempts::new(self);
+
+        loop {
+            if token.is_cancelled() {
+                return Err(CancelError::Cancelled);
+            }
+
+            match attempts.next(attempts.attempt(&mut retriable_block)) {
+                Next::Wait(backoff_time) => {
+                    if self.sleeper.sleep_cancellable(backoff_time, token) {
+                        return Err(CancelError::Cancelled);
+                    }
+                },
+                Next::Finish(report) => {
+                    return report.result.map_err(CancelError::Inner);
+                }
+            }
+        }
+    }
+
+    /// Executes an asynchronous operation, retrying it until it
+    /// succeeds or the maximum number of retries has been
+    /// exhausted. Rather than blocking the calling thread, the
+    /// time between attempts is waited out by `sleeper`, which
+    /// lets the same backoff drive retries on any runtime.
+    pub async fn retry_async<TSleeper, TRetriable, TFuture>(
+        &self,
+        sleeper: &TSleeper,
+        retriable_block: TRetriable
+    ) -> Result<T, E> where
+        TSleeper: AsyncSleeper + ?Sized,
+        TRetriable: FnMut() -> TFuture,
+        TFuture: Future<Output = Result<T, E>>
+    {
+        self.retry_async_with_report(sleeper, retriable_block).await.result
+    }
+
+    /// Executes an asynchronous operation like `retry_async`,
+    /// but reports on every attempt made rather than just the
+    /// final result.
+    pub async fn retry_async_with_report<TSleeper, TRetriable, TFuture>(
+        &self,
+        sleeper: &TSleeper,
+        mut retriable_block: TRetriable
+    ) -> RetryReport<T, E> where
+        TSleeper: AsyncSleeper + ?Sized,
+        TRetriable: FnMut() -> TFuture,
+        TFuture: Future<Output = Result<T, E>>
+    {
+        let mut attempts = Attempts::new(self);
+
+        loop {
+            match attempts.next(attempts.instrument(retriable_block()).await) {
+                Next::Wait(backoff_time) => sleeper.sleep(backoff_time).await,
+                Next::Finish(report) => return report
+            }
+        }
+    }
+
+    /// Polls an operation until it returns a value satisfying
+    /// `condition`, waiting between polls according to the
+    /// backoff schedule. Values which do not satisfy the
+    /// condition are always retried, while errors are retried
+    /// as `should_retry` decides. When the schedule is exhausted
+    /// first, the last value observed is returned in
+    /// `PollError::TimedOut`.
+    pub fn poll_until<TRetriable, TCondition>(
+        &self,
+        mut retriable_block: TRetriable,
+        condition: TCondition
+    ) -> Result<T, PollError<T, E>> where
+        TRetriable: FnMut() -> Result<T, E>,
+        TCondition: Fn(&T) -> bool
+    {
+        let mut attempts = Attempts::new(self);
+
+        loop {
+            match attempts.next_polled(attempts.attempt(&mut retriable_block), &condition) {
+                Next::Wait(backoff_time) => self.sleeper.sleep(backoff_time),
+                Next::Finish(report) => {
+                    return attempts.into_poll_result(report.result);
+                }
+            }
+        }
+    }
+
+    /// Polls an asynchronous operation like `poll_until`, with
+    /// the time between polls waited out by `sleeper`.
+    pub async fn poll_until_async<TSleeper, TRetriable, TFuture, TCondition>(
+        &self,
+        sleeper: &TSleeper,
+        mut retriable_block: TRetriable,
+        condition: TCondition
+    ) -> Result<T, PollError<T, E>> where
+        TSleeper: AsyncSleeper + ?Sized,
+        TRetriable: FnMut() -> TFuture,
+        TFuture: Future<Output = Result<T, E>>,
+        TCondition: Fn(&T) -> bool
+    {
+        let mut attempts = Attempts::new(self);
+
+        loop {
+            match attempts.next_polled(
+                attempts.instrument(retriable_block()).await, &condition
+            ) {
+                Next::Wait(backoff_time) => sleeper.sleep(backoff_time).await,
+                Next::Finish(report) => {
+                    return attempts.into_poll_result(report.result);
+                }
+            }
+        }
+    }
+}
+
+impl <T, E> Backoff for ExponentialBackoff<T, E> {
+    fn next_delay(&self, attempt: u32) -> Option<Duration> {
+        let backoff_time = match &self.schedule {
+            Some(schedule) => schedule.next_delay(attempt)?,
+            None if attempt >= u32::from(self.max_retries) => return None,
+            None => exponential_delay(
+                self.constant, self.coefficient, self.exponent, attempt
+            ).unwrap_or(Duration::MAX)
+        };
+
+        Some(self.max_delay.map_or(backoff_time, |max| backoff_time.min(max)))
+    }
+}
+
+/// Works out *an^b+c* for attempt *n*, or `None` when the
+/// result is too large to represent. The result is exact when
+/// *n^b* is a whole number. Otherwise *an^b* goes through an
+/// `f64` count of seconds, which is only precise to the
+/// nanosecond for waits shorter than about 104 days.
+pub(crate) fn exponential_delay(
+    constant: Duration,
+    coefficient: Duration,
+    exponent: f32,
+    attempt: u32
+) -> Option<Duration> {
+    let factor = f64::from(attempt).powf(f64::from(exponent));
+
+    let scaled = if factor.fract() == 0.0 && factor <= f64::from(u32::MAX) {
+        coefficient.checked_mul(factor as u32)?
+    } else {
+        Duration::try_from_secs_f64(coefficient.as_secs_f64() * factor).ok()?
+    };
+
+    constant.checked_add(scaled)
+}
+
+/// The state of a single call to one of the retry loops,
+/// shared between the blocking and asynchronous flavours.
+pub(crate) struct Attempts<'a, T, E> {
+    backoff: &'a ExponentialBackoff<T, E>,
+    breaker: Option<&'a CircuitBreaker>,
+    satisfied: bool,
+    retry_count: u32,
+    #[cfg(feature = "metrics")]
+    stopped: bool,
+    #[cfg(feature = "tracing")]
+    span: tracing::Span,
+    jitter: JitterState,
+    started: Instant,
+    delays: Vec<Duration>,
+    errors: Vec<(u32, E)>
+}
+
+/// What a retry loop should do after an attempt.
+pub(crate) enum Next<T, E> {
+
+    /// Wait this long, then make another attempt.
+    Wait(Duration),
+
+    /// Stop, handing this report back to the caller.
+    Finish(RetryReport<T, E>)
+}
+
+impl <'a, T, E> Attempts<'a, T, E> {
+    pub(crate) fn new(backoff: &'a ExponentialBackoff<T, E>) -> Attempts<'a, T, E> {
+        Attempts {
+            backoff,
+            breaker: None,
+            satisfied: true,
+            retry_count: 0,
+            #[cfg(feature = "metrics")]
+            stopped: false,
+            #[cfg(feature = "tracing")]
+            span: crate::trace::start(backoff),
+            jitter: JitterState::new(backoff.jitter, backoff.jitter_seed),
+            started: backoff.clock.now(),
+            delays: Vec::new(),
+            errors: Vec::new()
+        }
+    }
+
+    /// Counts every attempt towards `breaker`, and gives up
+    /// rather than retrying while it is open.
+    pub(crate) fn with_breaker(
+        mut self,
+        breaker: &'a CircuitBreaker
+    ) -> Attempts<'a, T, E> {
+        self.breaker = Some(breaker);
+        self
+    }
+
+    /// Makes an attempt inside the call's span, so that
+    /// anything the operation traces is recorded as part of
+    /// the retry.
+    pub(crate) fn attempt<R>(&self, retriable_block: impl FnOnce() -> R) -> R {
+        #[cfg(feature = "tracing")]
+        let _entered = self.span.enter();
+        retriable_block()
+    }
+
+    /// Attaches the call's span to an asynchronous attempt, as
+    /// `attempt` does for a blocking one.
+    #[cfg(feature = "tracing")]
+    pub(crate) fn instrument<F: Future>(
+        &self,
+        attempt: F
+    ) -> tracing::instrument::Instrumented<F> {
+        tracing::Instrument::instrument(attempt, self.span.clone())
+    }
+
+    /// Attaches the call's span to an asynchronous attempt, as
+    /// `attempt` does for a blocking one.
+    #[cfg(not(feature = "tracing"))]
+    pub(crate) fn instrument<F: Future>(&self, attempt: F) -> F {
+        attempt
+    }
+
+    /// Sorts the final `result` of polling into a satisfying
+    /// value, a timeout or an error.
+    pub(crate) fn into_poll_result(
+        self,
+        result: Result<T, E>
+    ) -> Result<T, PollError<T, E>> {
+        match result {
+            Ok(value) if self.satisfied => Ok(value),
+            Ok(value) => Err(PollError::TimedOut(value)),
+            Err(error) => Err(PollError::Failed(error))
+        }
+    }
+
+    /// Describes the attempt about to be made.
+    pub(crate) fn context(&self) -> RetryContext<'_, E> {
+        let elapsed = self.backoff.clock.now()
+            .saturating_duration_since(self.started);
+        let previous_error = match self.errors.last() {
+            Some((attempt, error)) if *attempt == self.retry_count => Some(error),
+            _ => None
+        };
+
+        RetryContext {
+            attempt: self.retry_count + 1,
+            elapsed,
+            remaining: self.backoff.max_elapsed
+                .map(|max_elapsed| max_elapsed.saturating_sub(elapsed)),
+            previous_error
+        }
+    }
+
+    /// Decides what to do after the latest attempt produced
+    /// `result`.
+    pub(crate) fn next(&mut self, result: Result<T, E>) -> Next<T, E> {
+        self.retry_count += 1;
+
+        #[cfg(feature = "metrics")]
+        crate::meter::attempted(self.backoff);
+
+        if let Some(breaker) = self.breaker {
+            breaker.record(result.is_ok());
+        }
+
+        if let (Ok(_), Some(retry_budget)) = (&result, &self.backoff.retry_budget) {
+            retry_budget.deposit();
+        }
+
+        match self.backoff_time(&result) {
+            Some(backoff_time) => {
+                if let Some(on_retry) = &self.backoff.on_retry {
+                    on_retry(self.retry_count, &result, backoff_time);
+                }
+                #[cfg(feature = "metrics")]
+                crate::meter::retrying(self.backoff);
+                #[cfg(feature = "tracing")]
+                crate::trace::retrying(
+                    &self.span, self.backoff, self.retry_count, &result, backoff_time
+                );
+                if let Err(error) = result {
+                    self.errors.push((self.retry_count, error));
+                }
+                self.delays.push(backoff_time);
+                Next::Wait(backoff_time)
+            },
+            None => Next::Finish(self.finish(result))
+        }
+    }
+
+    /// Decides what to do after the latest poll produced
+    /// `result`, retrying values which do not satisfy
+    /// `condition` as if they had failed.
+    pub(crate) fn next_polled(
+        &mut self,
+        result: Result<T, E>,
+        condition: &dyn Fn(&T) -> bool
+    ) -> Next<T, E> {
+        self.satisfied = result.as_ref().map_or(true, condition);
+        self.next(result)
+    }
+
+    /// Wraps up the call with the final attempt's `result`.
+    fn finish(&mut self, result: Result<T, E>) -> RetryReport<T, E> {
+        #[cfg(feature = "tracing")]
+        crate::trace::finished(&self.span, self.backoff, self.retry_count, &result);
+        #[cfg(feature = "metrics")]
+        crate::meter::finished(
+            self.backoff,
+            self.retry_count,
+            self.delays.iter().sum(),
+            result.is_ok() && self.satisfied,
+            self.stopped
+        );
+
+        match &result {
+            Ok(value) => if let (true, Some(on_success)) =
+                (self.satisfied, &self.backoff.on_success)
+            {
+                on_success(self.retry_count, value);
+            },
+            Err(error) => if let Some(on_give_up) = &self.backoff.on_give_up {
+                on_give_up(self.retry_count, error);
             }
         }
+
+        RetryReport {
+            result,
+            attempts: self.retry_count,
+            total_delay: self.delays.iter().sum(),
+            delays: std::mem::take(&mut self.delays),
+            errors: std::mem::take(&mut self.errors)
+        }
+    }
+
+    /// Returns the time to wait before retrying after `result`,
+    /// or `None` when it should be handed back to the caller.
+    fn backoff_time(&mut self, result: &Result<T, E>) -> Option<Duration> {
+        let decision = if self.satisfied {
+            (self.backoff.should_retry)(result)
+        } else {
+            Decision::Retry
+        };
+        #[cfg(feature = "metrics")]
+        {
+            self.stopped = decision == Decision::Stop;
+        }
+        if decision == Decision::Stop {
+            return None;
+        }
+
+        let backoff_time = self.backoff.next_delay(self.retry_count)?;
+        let backoff_time = match decision {
+            Decision::RetryAfter(retry_after) => retry_after,
+            _ => self.jitter.apply(backoff_time, self.backoff.max_delay)
+        };
+
+        if self.breaker.is_some_and(CircuitBreaker::is_open) {
+            return None;
+        }
+
+        if let Some(max_elapsed) = self.backoff.max_elapsed {
+            let elapsed = self.backoff.clock.now()
+                .saturating_duration_since(self.started);
+            let overruns = elapsed.checked_add(backoff_time)
+                .is_none_or(|total| total > max_elapsed);
+            if overruns {
+                return None;
+            }
+        }
+
+        if let Some(retry_budget) = &self.backoff.retry_budget {
+            if !retry_budget.try_withdraw() {
+                return None;
+            }
+        }
+
+        Some(backoff_time)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::ExponentialBackoff;
+    use crate::{
+        Backoff, Decision, ExponentialBackoff, Jitter, ListBackoff, MockClock
+    };
+    use std::future::ready;
+    use std::io;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
 
     #[test]
     fn succeeds_after_two_retries() {
@@ -93,10 +885,7 @@ mod tests {
             vec![false, false, true]
         );
 
-        match result {
-            Ok(_) => assert!(true),
-            Err(_) => assert!(false)
-        };
+        assert!(result.is_ok());
     }
 
     #[test]
@@ -108,31 +897,297 @@ mod tests {
 
         let result = retry_until_true(v);
 
-        match result {
-            Ok(_) => assert!(false), // succeeded? impossible!
-            Err(_) => assert!(true) // failed as expected
-        };
+        // succeeded? impossible!
+        assert!(result.is_err());
     }
 
-    fn retry_until_true(
-        mut v: Vec<bool>
-    ) -> Result<bool, bool> {
+    #[tokio::test]
+    async fn async_succeeds_after_two_retries() {
+        let sleeper = MockClock::new();
+        let mut v = vec![true, false, false];
+
+        let result = test_backoff().retry_async(&sleeper, || {
+            ready(next_result(&mut v))
+        }).await;
+
+        assert!(result.is_ok());
+        assert_eq!(
+            sleeper.sleeps(),
+            vec![Duration::from_millis(1), Duration::from_millis(4)]
+        );
+    }
+
+    #[tokio::test]
+    async fn async_fails_after_exhausting_retries() {
+        let sleeper = MockClock::new();
+        let mut v = vec![false; 8];
+
+        let result = test_backoff().retry_async(&sleeper, || {
+            ready(next_result(&mut v))
+        }).await;
+
+        assert!(result.is_err());
+        assert_eq!(sleeper.sleeps().len(), 6);
+    }
+
+    #[tokio::test]
+    async fn seeded_jitter_repeats_exact_waits() {
         let backoff = ExponentialBackoff::new(
-            // tighten the timings to make the tests run faster
-            7, 0.0, 1.0, 2.0,
-            // retry until there is no "error"
-            |result: &Result<bool, bool>| !result.is_ok()
+            5, Duration::ZERO, Duration::from_millis(100), 2.0,
+            |result: &Result<bool, bool>| result.is_err()
+        ).with_jitter(Jitter::Full).with_jitter_seed(1);
+
+        for _ in 0..2 {
+            let sleeper = MockClock::new();
+            let result = backoff.retry_async(
+                &sleeper, || ready(Err::<bool, bool>(false))
+            ).await;
+
+            assert!(result.is_err());
+            let sleeps: Vec<u128> = sleeper.sleeps()
+                .iter().map(Duration::as_nanos).collect();
+            assert_eq!(
+                sleeps,
+                vec![56_656_158, 298_312_703, 873_902_479, 710_974_747]
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn follows_custom_schedule() {
+        let sleeper = MockClock::new();
+        let delays = vec![
+            Duration::from_millis(3), Duration::from_millis(1)
+        ];
+        let backoff = ExponentialBackoff::from_schedule(
+            ListBackoff::new(delays.clone()),
+            |result: &Result<bool, bool>| result.is_err()
         );
 
+        let result = backoff.retry_async(
+            &sleeper, || ready(Err::<bool, bool>(false))
+        ).await;
+
+        assert!(result.is_err());
+        assert_eq!(sleeper.sleeps(), delays);
+    }
+
+    #[tokio::test]
+    async fn caps_each_backoff_time() {
+        let sleeper = MockClock::new();
+        let backoff = ExponentialBackoff::new(
+            4, Duration::ZERO, Duration::from_secs(1), 3.0,
+            |result: &Result<bool, bool>| result.is_err()
+        ).with_max_delay(Duration::from_secs(5));
+
+        let result = backoff.retry_async(
+            &sleeper, || ready(Err::<bool, bool>(false))
+        ).await;
+
+        assert!(result.is_err());
+        assert_eq!(sleeper.sleeps(), vec![
+            Duration::from_secs(1),
+            Duration::from_secs(5),
+            Duration::from_secs(5)
+        ]);
+    }
+
+    #[test]
+    fn gives_up_before_exceeding_max_elapsed() {
+        let clock = MockClock::new();
+        let backoff = ExponentialBackoff::new(
+            7, Duration::from_millis(30), Duration::ZERO, 1.0,
+            |result: &Result<bool, bool>| result.is_err()
+        )
+            .with_clock(clock.clone())
+            .with_sleeper(clock.clone())
+            .with_max_elapsed(Duration::from_millis(100));
+        let mut attempts = 0;
+
         let result = backoff.retry(|| {
-            return match v.pop() {
-                Some(true) => Ok(true),
-                Some(false) => Err(false),
-                None => Err(false)
-            };
+            attempts += 1;
+            clock.advance(Duration::from_millis(10));
+            Err::<bool, bool>(false)
         });
 
-        return result;
+        assert!(result.is_err());
+        assert_eq!(attempts, 3);
+        assert_eq!(clock.sleeps(), vec![Duration::from_millis(30); 2]);
     }
-}
 
+    #[test]
+    fn gives_up_on_waits_too_long_to_add_up() {
+        let clock = MockClock::new();
+        let backoff = ExponentialBackoff::new(
+            7, Duration::ZERO, Duration::from_millis(1), 1.0,
+            |_: &Result<bool, bool>| Decision::RetryAfter(Duration::MAX)
+        )
+            .with_clock(clock.clone())
+            .with_sleeper(clock.clone())
+            .with_max_elapsed(Duration::from_secs(10));
+
+        let result = backoff.retry(|| {
+            clock.advance(Duration::from_secs(1));
+            Err::<bool, bool>(false)
+        });
+
+        assert!(result.is_err());
+        assert!(clock.sleeps().is_empty());
+    }
+
+    #[test]
+    fn reports_every_attempt() {
+        let mut v = vec![true, false, false];
+
+        let report = test_backoff().retry_with_report(|| next_result(&mut v));
+
+        assert_eq!(report.result, Ok(true));
+        assert_eq!(report.attempts, 3);
+        assert_eq!(report.delays, vec![
+            Duration::from_millis(1), Duration::from_millis(4)
+        ]);
+        assert_eq!(report.total_delay, Duration::from_millis(5));
+        assert_eq!(report.errors, vec![(1, false), (2, false)]);
+    }
+
+    #[test]
+    fn calls_hooks() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let (retries, give_ups, successes) =
+            (events.clone(), events.clone(), events.clone());
+        let backoff = test_backoff()
+            .with_on_retry(move |attempt, result, delay| {
+                retries.lock().unwrap().push(
+                    format!("retry {} {:?} {:?}", attempt, result, delay)
+                );
+            })
+            .with_on_give_up(move |attempts, error| {
+                give_ups.lock().unwrap().push(
+                    format!("give up {} {:?}", attempts, error)
+                );
+            })
+            .with_on_success(move |attempts, value| {
+                successes.lock().unwrap().push(
+                    format!("success {} {:?}", attempts, value)
+                );
+            });
+
+        let mut v = vec![true, false];
+        assert!(backoff.retry(|| next_result(&mut v)).is_ok());
+        assert!(backoff.retry(|| Err::<bool, bool>(true)).is_err());
+
+        let events = events.lock().unwrap();
+        assert_eq!(events[..2], [
+            "retry 1 Err(false) 1ms", "success 2 true"
+        ]);
+        assert_eq!(events.len(), 2 + 6 + 1);
+        assert_eq!(events.last().unwrap(), "give up 7 true");
+    }
+
+    #[tokio::test]
+    async fn honours_decisions() {
+        let sleeper = MockClock::new();
+        let backoff = ExponentialBackoff::new(
+            7, Duration::ZERO, Duration::from_millis(1), 2.0,
+            |result: &Result<bool, u64>| match result {
+                Ok(_) => Decision::Stop,
+                Err(0) => Decision::Stop,
+                Err(1) => Decision::Retry,
+                Err(seconds) => Decision::RetryAfter(
+                    Duration::from_secs(*seconds)
+                )
+            }
+        );
+        let mut errors = vec![0, 1, 30];
+
+        let result = backoff.retry_async(
+            &sleeper, || ready(Err::<bool, u64>(errors.pop().unwrap()))
+        ).await;
+
+        assert_eq!(result, Err(0));
+        assert_eq!(sleeper.sleeps(), vec![
+            Duration::from_secs(30), Duration::from_millis(4)
+        ]);
+    }
+
+    #[test]
+    fn backoff_times_are_exact_at_any_scale() {
+        let micros = ExponentialBackoff::<(), ()>::new(
+            7, Duration::from_micros(3), Duration::from_micros(250), 1.0,
+            |_: &Result<(), ()>| true
+        );
+        let minutes = ExponentialBackoff::<(), ()>::new(
+            7, Duration::from_nanos(1), Duration::from_secs(600), 0.5,
+            |_: &Result<(), ()>| true
+        );
+        let huge = ExponentialBackoff::<(), ()>::new(
+            7, Duration::MAX, Duration::MAX, 2.0,
+            |_: &Result<(), ()>| true
+        );
+
+        assert_eq!(micros.next_delay(3), Some(Duration::from_micros(753)));
+        assert_eq!(
+            minutes.next_delay(4),
+            Some(Duration::from_secs(1200) + Duration::from_nanos(1))
+        );
+        assert_eq!(
+            minutes.next_delay(2),
+            Some(Duration::from_nanos(848_528_137_424 + 1))
+        );
+        assert_eq!(huge.next_delay(3), Some(Duration::MAX));
+    }
+
+    #[test]
+    fn retries_transient_errors() {
+        let backoff = ExponentialBackoff::for_transient_errors()
+            .with_sleeper(MockClock::new());
+        let mut errors = vec![
+            io::ErrorKind::NotFound, io::ErrorKind::TimedOut
+        ];
+
+        let report = backoff.retry_with_report(|| {
+            Err::<(), _>(io::Error::from(errors.pop().unwrap()))
+        });
+
+        assert_eq!(report.attempts, 2);
+        assert_eq!(report.result.unwrap_err().kind(), io::ErrorKind::NotFound);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test(start_paused = true)]
+    async fn tokio_sleeper_waits_between_attempts() {
+        let start = tokio::time::Instant::now();
+        let mut v = vec![true, false, false];
+
+        let result = test_backoff().retry_async(
+            &crate::TokioSleeper, || ready(next_result(&mut v))
+        ).await;
+
+        assert!(result.is_ok());
+        assert_eq!(start.elapsed(), Duration::from_millis(5));
+    }
+
+    fn retry_until_true(
+        mut v: Vec<bool>
+    ) -> Result<bool, bool> {
+        test_backoff().retry(|| next_result(&mut v))
+    }
+
+    fn test_backoff() -> ExponentialBackoff<bool, bool> {
+        ExponentialBackoff::new(
+            7, Duration::ZERO, Duration::from_millis(1), 2.0,
+            // retry until there is no "error"
+            |result: &Result<bool, bool>| result.is_err()
+        )
+            // sleep on a mock clock to make the tests run instantly
+            .with_sleeper(MockClock::new())
+    }
+
+    fn next_result(v: &mut Vec<bool>) -> Result<bool, bool> {
+        match v.pop() {
+            Some(true) => Ok(true),
+            Some(false) => Err(false),
+            None => Err(false)
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use std::time::Duration;
+
+use crate::ExponentialBackoff;
+
+/// The label value used for backoffs without an operation name.
+const UNNAMED: &str = "unnamed";
+
+fn operation<T, E>(backoff: &ExponentialBackoff<T, E>) -> String {
+    backoff.operation.clone().unwrap_or_else(|| String::from(UNNAMED))
+}
+
+/// Counts an attempt at the operation.
+pub(crate) fn attempted<T, E>(backoff: &ExponentialBackoff<T, E>) {
+    ::metrics::counter!("retry_attempts_total", "operation" => operation(backoff))
+        .increment(1);
+}
+
+/// Counts an attempt which is about to be retried.
+pub(crate) fn retrying<T, E>(backoff: &ExponentialBackoff<T, E>) {
+    ::metrics::counter!("retry_retries_total", "operation" => operation(backoff))
+        .increment(1);
+}
+
+/// Records the outcome of a call which made `attempts`
+/// attempts and waited `total_delay` between them. `stopped`
+/// says whether the final result was one `should_retry`
+/// refused to retry, rather than one the schedule, budget or
+/// deadline ran out on.
+pub(crate) fn finished<T, E>(
+    backoff: &ExponentialBackoff<T, E>,
+    attempts: u32,
+    total_delay: Duration,
+    succeeded: bool,
+    stopped: bool
+) {
+    let operation = operation(backoff);
+
+    let outcome = match (succeeded, stopped) {
+        (true, _) if attempts > 1 => Some("retry_successes_after_retry_total"),
+        (true, _) => None,
+        (false, true) => Some("retry_non_retriable_failures_total"),
+        (false, false) => Some("retry_exhaustions_total")
+    };
+    if let Some(outcome) = outcome {
+        ::metrics::counter!(outcome, "operation" => operation.clone()).increment(1);
+    }
+
+    ::metrics::histogram!("retry_attempts_per_call", "operation" => operation.clone())
+        .record(f64::from(attempts));
+    ::metrics::histogram!("retry_wait_seconds", "operation" => operation)
+        .record(total_delay.as_secs_f64());
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Decision, ExponentialBackoff, MockClock};
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+    use std::collections::HashMap;
+    use std::time::Duration;
+
+    /// Runs `run` against a fresh recorder, returning what it
+    /// recorded by metric name and operation label.
+    fn measured(run: impl FnOnce()) -> HashMap<(String, String), DebugValue> {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+        metrics::with_local_recorder(&recorder, run);
+
+        snapshotter.snapshot().into_vec().into_iter().map(|(key, _, _, value)| {
+            let key = key.key();
+            let operation = key.labels()
+                .find(|label| label.key() == "operation")
+                .map(|label| label.value().to_string())
+                .unwrap_or_default();
+            ((key.name().to_string(), operation), value)
+        }).collect()
+    }
+
+    fn counter(
+        metrics: &HashMap<(String, String), DebugValue>,
+        name: &str,
+        operation: &str
+    ) -> Option<u64> {
+        match metrics.get(&(name.to_string(), operation.to_string())) {
+            Some(DebugValue::Counter(count)) => Some(*count),
+            _ => None
+        }
+    }
+
+    fn histogram(
+        metrics: &HashMap<(String, String), DebugValue>,
+        name: &str,
+        operation: &str
+    ) -> Vec<f64> {
+        match metrics.get(&(name.to_string(), operation.to_string())) {
+            Some(DebugValue::Histogram(values)) => {
+                values.iter().map(|value| value.into_inner()).collect()
+            },
+            _ => Vec::new()
+        }
+    }
+
+    fn backoff(operation: &str) -> ExponentialBackoff<u32, &'static str> {
+        ExponentialBackoff::new(
+            3, Duration::ZERO, Duration::from_millis(100), 1.0,
+            |result: &Result<u32, &str>| match result {
+                Err("fatal") => Decision::Stop,
+                result => result.is_err().into()
+            }
+        ).with_sleeper(MockClock::new()).with_operation(operation)
+    }
+
+    #[test]
+    fn counts_attempts_and_outcomes_by_operation() {
+        let metrics = measured(|| {
+            let mut attempts = 0;
+            let _ = backoff("fetch").retry(|| {
+                attempts += 1;
+                if attempts < 3 { Err("flaky") } else { Ok(attempts) }
+            });
+            let _ = backoff("store").retry(|| Err::<u32, _>("flaky"));
+            let _ = backoff("store").retry(|| Err::<u32, _>("fatal"));
+        });
+
+        assert_eq!(counter(&metrics, "retry_attempts_total", "fetch"), Some(3));
+        assert_eq!(counter(&metrics, "retry_retries_total", "fetch"), Some(2));
+        assert_eq!(counter(&metrics, "retry_successes_after_retry_total", "fetch"), Some(1));
+        assert_eq!(counter(&metrics, "retry_exhaustions_total", "fetch"), None);
+
+        assert_eq!(counter(&metrics, "retry_attempts_total", "store"), Some(4));
+        assert_eq!(counter(&metrics, "retry_exhaustions_total", "store"), Some(1));
+        assert_eq!(counter(&metrics, "retry_non_retriable_failures_total", "store"), Some(1));
+
+        assert_eq!(histogram(&metrics, "retry_attempts_per_call", "fetch"), vec![3.0]);
+        assert_eq!(histogram(&metrics, "retry_wait_seconds", "fetch"), vec![0.3]);
+        assert_eq!(histogram(&metrics, "retry_attempts_per_call", "store"), vec![3.0, 1.0]);
+    }
+
+    #[test]
+    fn labels_unnamed_operations() {
+        let metrics = measured(|| {
+            let backoff = ExponentialBackoff::new(
+                3, Duration::ZERO, Duration::from_millis(100), 1.0,
+                |result: &Result<(), ()>| result.is_err()
+            );
+            let _ = backoff.retry(|| Ok(()));
+        });
+
+        assert_eq!(counter(&metrics, "retry_attempts_total", "unnamed"), Some(1));
+        assert_eq!(counter(&metrics, "retry_successes_after_retry_total", "unnamed"), None);
+    }
+}
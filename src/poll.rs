@@ -0,0 +1,110 @@
+use std::error::Error;
+use std::fmt;
+
+/// The error returned by `ExponentialBackoff::poll_until` when
+/// polling does not end with a value satisfying the condition.
+#[derive(Debug, PartialEq, Eq)]
+pub enum PollError<T, E> {
+
+    /// The schedule was exhausted while the operation was still
+    /// returning values which did not satisfy the condition.
+    /// Holds the last value observed.
+    TimedOut(T),
+
+    /// The operation failed, and the error was not retried.
+    Failed(E)
+}
+
+impl <T: fmt::Debug, E: fmt::Display> fmt::Display for PollError<T, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PollError::TimedOut(last) => {
+                write!(f, "timed out polling, last observed {:?}", last)
+            },
+            PollError::Failed(error) => error.fmt(f)
+        }
+    }
+}
+
+impl <T: fmt::Debug, E: Error + 'static> Error for PollError<T, E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PollError::TimedOut(_) => None,
+            PollError::Failed(error) => Some(error)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ExponentialBackoff, MockClock, PollError};
+    use std::future::ready;
+    use std::time::Duration;
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    enum Job {
+        Queued,
+        Running,
+        Done
+    }
+
+    fn polling_backoff() -> ExponentialBackoff<Job, &'static str> {
+        ExponentialBackoff::new(
+            3, Duration::ZERO, Duration::from_millis(1), 2.0, |r| r.is_err()
+        ).with_sleeper(MockClock::new())
+    }
+
+    #[test]
+    fn polls_until_condition_holds() {
+        let mut states = vec![Job::Done, Job::Running, Job::Queued];
+        let result = polling_backoff().poll_until(
+            || Ok(states.pop().unwrap()),
+            |job| *job == Job::Done
+        );
+
+        assert_eq!(result, Ok(Job::Done));
+        assert!(states.is_empty());
+    }
+
+    #[test]
+    fn times_out_with_last_value() {
+        let mut polls = 0;
+        let result = polling_backoff().poll_until(
+            || {
+                polls += 1;
+                Ok(Job::Running)
+            },
+            |job| *job == Job::Done
+        );
+
+        assert_eq!(result, Err(PollError::TimedOut(Job::Running)));
+        assert_eq!(polls, 3);
+    }
+
+    #[test]
+    fn stops_on_unretried_errors() {
+        let backoff: ExponentialBackoff<Job, _> = ExponentialBackoff::new(
+            3, Duration::ZERO, Duration::from_millis(1), 2.0, |_| false
+        ).with_sleeper(MockClock::new());
+        let result = backoff.poll_until(
+            || Err("gone"),
+            |job| *job == Job::Done
+        );
+
+        assert_eq!(result, Err(PollError::Failed("gone")));
+    }
+
+    #[tokio::test]
+    async fn polls_asynchronously() {
+        let clock = MockClock::new();
+        let mut states = vec![Job::Done, Job::Queued];
+        let result = polling_backoff().poll_until_async(
+            &clock,
+            || ready(Ok(states.pop().unwrap())),
+            |job| *job == Job::Done
+        ).await;
+
+        assert_eq!(result, Ok(Job::Done));
+        assert_eq!(clock.sleeps(), vec![Duration::from_millis(1)]);
+    }
+}
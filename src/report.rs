@@ -0,0 +1,144 @@
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+
+/// Everything that happened while retrying an operation: the
+/// final result, how many attempts it took, how long was
+/// spent waiting between them, and the errors that were
+/// retried along the way.
+#[derive(Debug)]
+pub struct RetryReport<T, E> {
+
+    /// The result of the final attempt.
+    pub result: Result<T, E>,
+
+    /// The number of attempts made, including the final one.
+    pub attempts: u32,
+
+    /// The total time spent waiting between attempts.
+    pub total_delay: Duration,
+
+    /// The time waited before each retry, in order.
+    pub delays: Vec<Duration>,
+
+    /// The errors returned by attempts before the final one,
+    /// oldest first, each paired with the number of the
+    /// attempt which returned it.
+    pub errors: Vec<(u32, E)>
+}
+
+impl <T, E> RetryReport<T, E> {
+
+    /// Converts the report into its final result, folding any
+    /// intermediate errors into a `RetryError` when the final
+    /// attempt failed too.
+    pub fn into_result(self) -> Result<T, RetryError<E>> {
+        let error = match self.result {
+            Ok(value) => return Ok(value),
+            Err(error) => error
+        };
+
+        let mut previous = None;
+        for (attempt, error) in self.errors {
+            previous = Some(Box::new(RetryError { attempt, error, previous }));
+        }
+
+        Err(RetryError { attempt: self.attempts, error, previous })
+    }
+}
+
+/// The error from one failed attempt, linked to the error from
+/// the attempt before it. As a `std::error::Error`, its
+/// `source` is the previous attempt's `RetryError`, so walking
+/// the chain visits every attempt from the last to the first.
+#[derive(Debug)]
+pub struct RetryError<E> {
+
+    /// The number of the attempt which failed, counting from 1.
+    pub attempt: u32,
+
+    /// The error returned by the attempt.
+    pub error: E,
+
+    /// The failure of the previous attempt, if there was one
+    /// and it returned an error.
+    pub previous: Option<Box<RetryError<E>>>
+}
+
+impl <E> RetryError<E> {
+
+    /// Iterates over the errors of every failed attempt, from
+    /// the last to the first.
+    pub fn errors(&self) -> impl Iterator<Item = &E> {
+        let mut next = Some(self);
+
+        std::iter::from_fn(move || {
+            let current = next?;
+            next = current.previous.as_deref();
+            Some(&current.error)
+        })
+    }
+}
+
+impl <E: fmt::Display> fmt::Display for RetryError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "attempt {} failed: {}", self.attempt, self.error)
+    }
+}
+
+impl <E: Error + 'static> Error for RetryError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match &self.previous {
+            Some(previous) => Some(previous.as_ref()),
+            None => self.error.source()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::report::RetryReport;
+    use std::error::Error;
+    use std::fmt;
+    use std::time::Duration;
+
+    #[derive(Debug, PartialEq)]
+    struct Failure(u32);
+
+    impl fmt::Display for Failure {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "failure {}", self.0)
+        }
+    }
+
+    impl Error for Failure {}
+
+    #[test]
+    fn error_chain_walks_every_attempt() {
+        let report = RetryReport::<(), Failure> {
+            result: Err(Failure(3)),
+            attempts: 3,
+            total_delay: Duration::from_millis(3),
+            delays: vec![Duration::from_millis(1), Duration::from_millis(2)],
+            errors: vec![(1, Failure(1)), (2, Failure(2))]
+        };
+
+        let error = report.into_result().unwrap_err();
+        let mut chain = vec![error.to_string()];
+        let mut source = error.source();
+        while let Some(error) = source {
+            chain.push(error.to_string());
+            source = error.source();
+        }
+
+        assert_eq!(chain, vec![
+            "attempt 3 failed: failure 3",
+            "attempt 2 failed: failure 2",
+            "attempt 1 failed: failure 1"
+        ]);
+        assert_eq!(
+            error.errors().collect::<Vec<_>>(),
+            vec![&Failure(3), &Failure(2), &Failure(1)]
+        );
+    }
+}
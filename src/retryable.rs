@@ -0,0 +1,167 @@
+use std::error::Error;
+use std::io;
+use std::time::Duration;
+
+use crate::Decision;
+
+/// Errors which know whether they are worth retrying, so that
+/// the decision can be made once, next to the error type,
+/// rather than in a `should_retry` block at every call site.
+/// Backoffs built with `ExponentialBackoff::for_transient_errors`,
+/// or given `retry_transient` as their `should_retry` block,
+/// classify errors through this trait.
+pub trait Retryable {
+
+    /// Whether the error is likely to go away if the operation
+    /// is tried again.
+    fn is_transient(&self) -> bool;
+
+    /// Decides how to handle the error: by default, retry on
+    /// the backoff schedule if it is transient and stop
+    /// otherwise. Errors which know how long to wait, such as
+    /// responses carrying a `Retry-After` header, can override
+    /// this to return `Decision::RetryAfter`.
+    fn retry_decision(&self) -> Decision {
+        Decision::from(self.is_transient())
+    }
+}
+
+/// A `should_retry` block which retries the errors that
+/// `Retryable` considers worth retrying, and stops on success.
+pub fn retry_transient<T, E: Retryable>(result: &Result<T, E>) -> Decision {
+    match result {
+        Ok(_) => Decision::Stop,
+        Err(error) => error.retry_decision()
+    }
+}
+
+/// Whether `kind` describes a failure that may well succeed if
+/// tried again: an interrupted or would-block operation, a
+/// timeout, or a connection that was dropped or refused.
+fn is_transient_kind(kind: io::ErrorKind) -> bool {
+    matches!(
+        kind,
+        io::ErrorKind::Interrupted
+            | io::ErrorKind::WouldBlock
+            | io::ErrorKind::TimedOut
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::BrokenPipe
+    )
+}
+
+/// Walks `error` and its `source` chain, returning whether any
+/// of them is an `io::Error` of a transient kind. This finds
+/// the I/O failure underneath errors which wrap it, such as an
+/// `io::Error` of kind `Other` carrying a TLS error which was
+/// itself caused by a connection reset.
+pub fn is_transient_error(error: &(dyn Error + 'static)) -> bool {
+    let mut next = Some(error);
+
+    while let Some(error) = next {
+        if let Some(io_error) = error.downcast_ref::<io::Error>() {
+            if is_transient_kind(io_error.kind()) {
+                return true;
+            }
+        }
+        next = error.source();
+    }
+
+    false
+}
+
+impl Retryable for io::Error {
+    fn is_transient(&self) -> bool {
+        is_transient_kind(self.kind())
+            || self.get_ref().is_some_and(|inner| is_transient_error(inner))
+    }
+}
+
+impl Retryable for Box<dyn Error + Send + Sync> {
+    fn is_transient(&self) -> bool {
+        is_transient_error(self.as_ref())
+    }
+}
+
+impl Retryable for Box<dyn Error> {
+    fn is_transient(&self) -> bool {
+        is_transient_error(self.as_ref())
+    }
+}
+
+/// Reads the wait out of a field named by the derive macro's
+/// `#[retry(after = "field")]` attribute. Not public API.
+#[doc(hidden)]
+pub trait RetryAfterValue {
+    fn retry_after(&self) -> Option<Duration>;
+}
+
+impl RetryAfterValue for Duration {
+    fn retry_after(&self) -> Option<Duration> {
+        Some(*self)
+    }
+}
+
+impl RetryAfterValue for Option<Duration> {
+    fn retry_after(&self) -> Option<Duration> {
+        *self
+    }
+}
+
+impl <T: RetryAfterValue + ?Sized> RetryAfterValue for &T {
+    fn retry_after(&self) -> Option<Duration> {
+        (**self).retry_after()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{retry_transient, Decision, Retryable};
+    use std::error::Error;
+    use std::fmt;
+    use std::io;
+
+    #[derive(Debug)]
+    struct Wrapper(io::Error);
+
+    impl fmt::Display for Wrapper {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "wrapped")
+        }
+    }
+
+    impl Error for Wrapper {
+        fn source(&self) -> Option<&(dyn Error + 'static)> {
+            Some(&self.0)
+        }
+    }
+
+    #[test]
+    fn classifies_io_errors_by_kind() {
+        let reset = io::Error::from(io::ErrorKind::ConnectionReset);
+        let missing = io::Error::from(io::ErrorKind::NotFound);
+
+        assert!(reset.is_transient());
+        assert!(!missing.is_transient());
+        assert_eq!(retry_transient::<(), _>(&Err(reset)), Decision::Retry);
+        assert_eq!(retry_transient::<(), _>(&Err(missing)), Decision::Stop);
+        assert_eq!(retry_transient::<(), io::Error>(&Ok(())), Decision::Stop);
+    }
+
+    #[test]
+    fn walks_source_chains() {
+        let timed_out = Wrapper(io::Error::from(io::ErrorKind::TimedOut));
+        let wrapped = io::Error::other(timed_out);
+        let boxed: Box<dyn Error + Send + Sync> = Box::new(Wrapper(
+            io::Error::from(io::ErrorKind::Interrupted)
+        ));
+        let permanent: Box<dyn Error + Send + Sync> = Box::new(Wrapper(
+            io::Error::from(io::ErrorKind::PermissionDenied)
+        ));
+
+        assert!(wrapped.is_transient());
+        assert!(boxed.is_transient());
+        assert!(!permanent.is_transient());
+    }
+}
@@ -0,0 +1,79 @@
+use std::future::Future;
+use std::thread;
+use std::time::Duration;
+
+use crate::cancel::CancellationToken;
+
+/// A source of blocking delays, which `ExponentialBackoff::retry`
+/// uses to wait between attempts.
+pub trait Sleeper {
+
+    /// Blocks the calling thread until `duration` has elapsed.
+    fn sleep(&self, duration: Duration);
+
+    /// Blocks the calling thread until `duration` has elapsed
+    /// or `token` is cancelled, returning whether it was
+    /// cancelled. By default this waits on the token itself
+    /// rather than calling `sleep`, since a sleep cannot be
+    /// interrupted.
+    fn sleep_cancellable(
+        &self,
+        duration: Duration,
+        token: &CancellationToken
+    ) -> bool {
+        token.wait_timeout(duration)
+    }
+}
+
+/// Waits between attempts using `std::thread::sleep`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ThreadSleeper;
+
+impl Sleeper for ThreadSleeper {
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration);
+    }
+}
+
+/// A source of asynchronous delays, which lets
+/// `ExponentialBackoff::retry_async` wait between attempts
+/// without tying the crate to any particular runtime.
+pub trait AsyncSleeper {
+
+    /// The future returned by `sleep`.
+    type Sleep: Future<Output = ()>;
+
+    /// Returns a future which completes once `duration` has
+    /// elapsed.
+    fn sleep(&self, duration: Duration) -> Self::Sleep;
+}
+
+/// Waits between attempts using `tokio::time::sleep`. Must be
+/// used from within a tokio runtime with the time driver
+/// enabled.
+#[cfg(feature = "tokio")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TokioSleeper;
+
+#[cfg(feature = "tokio")]
+impl AsyncSleeper for TokioSleeper {
+    type Sleep = tokio::time::Sleep;
+
+    fn sleep(&self, duration: Duration) -> tokio::time::Sleep {
+        tokio::time::sleep(duration)
+    }
+}
+
+/// Waits between attempts using `async_std::task::sleep`.
+#[cfg(feature = "async-std")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AsyncStdSleeper;
+
+#[cfg(feature = "async-std")]
+impl AsyncSleeper for AsyncStdSleeper {
+    type Sleep = std::pin::Pin<Box<dyn Future<Output = ()> + Send>>;
+
+    fn sleep(&self, duration: Duration) -> Self::Sleep {
+        Box::pin(async_std::task::sleep(duration))
+    }
+}
@@ -0,0 +1,303 @@
+//! Retries for `tower` services, enabled by the `tower`
+//! feature. `RetryLayer` wraps a service so that every call
+//! is retried according to an `ExponentialBackoff`, cloning the
+//! request for each attempt and waiting between attempts with
+//! an `AsyncSleeper`.
+
+use std::fmt;
+use std::future::{poll_fn, Future};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::{AsyncSleeper, ExponentialBackoff};
+
+/// Wraps services in a `RetryService`.
+pub struct RetryLayer<TResponse, TError, TSleeper> {
+    backoff: Arc<ExponentialBackoff<TResponse, TError>>,
+    sleeper: TSleeper
+}
+
+impl <TResponse, TError, TSleeper> RetryLayer<TResponse, TError, TSleeper> {
+
+    /// Creates a layer which retries calls according to
+    /// `backoff`, waiting between attempts with `sleeper`. The
+    /// backoff's `should_retry` block sees each attempt's
+    /// result, so it can retry on error responses as well as
+    /// on errors.
+    pub fn new(
+        backoff: Arc<ExponentialBackoff<TResponse, TError>>,
+        sleeper: TSleeper
+    ) -> RetryLayer<TResponse, TError, TSleeper> {
+        RetryLayer { backoff, sleeper }
+    }
+}
+
+impl <TResponse, TError, TSleeper: Clone> Clone for RetryLayer<TResponse, TError, TSleeper> {
+    fn clone(&self) -> Self {
+        RetryLayer {
+            backoff: self.backoff.clone(),
+            sleeper: self.sleeper.clone()
+        }
+    }
+}
+
+impl <TResponse, TError, TSleeper> fmt::Debug for RetryLayer<TResponse, TError, TSleeper> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RetryLayer").finish_non_exhaustive()
+    }
+}
+
+impl <TService, TResponse, TError, TSleeper: Clone> Layer<TService>
+    for RetryLayer<TResponse, TError, TSleeper> {
+    type Service = RetryService<TService, TResponse, TError, TSleeper>;
+
+    fn layer(&self, inner: TService) -> Self::Service {
+        RetryService {
+            inner,
+            backoff: self.backoff.clone(),
+            sleeper: self.sleeper.clone()
+        }
+    }
+}
+
+/// A service which retries calls to the service it wraps.
+/// Every attempt is made on a clone of the inner service with
+/// a clone of the request, so both must be `Clone`.
+pub struct RetryService<TService, TResponse, TError, TSleeper> {
+    inner: TService,
+    backoff: Arc<ExponentialBackoff<TResponse, TError>>,
+    sleeper: TSleeper
+}
+
+impl <TService, TResponse, TError, TSleeper> RetryService<TService, TResponse, TError, TSleeper> {
+
+    /// Wraps `inner`, retrying its calls according to `backoff`
+    /// and waiting between attempts with `sleeper`.
+    pub fn new(
+        inner: TService,
+        backoff: Arc<ExponentialBackoff<TResponse, TError>>,
+        sleeper: TSleeper
+    ) -> RetryService<TService, TResponse, TError, TSleeper> {
+        RetryService { inner, backoff, sleeper }
+    }
+}
+
+impl <TService: Clone, TResponse, TError, TSleeper: Clone> Clone
+    for RetryService<TService, TResponse, TError, TSleeper> {
+    fn clone(&self) -> Self {
+        RetryService {
+            inner: self.inner.clone(),
+            backoff: self.backoff.clone(),
+            sleeper: self.sleeper.clone()
+        }
+    }
+}
+
+impl <TService: fmt::Debug, TResponse, TError, TSleeper> fmt::Debug
+    for RetryService<TService, TResponse, TError, TSleeper> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RetryService")
+            .field("inner", &self.inner)
+            .finish_non_exhaustive()
+    }
+}
+
+impl <TService, TRequest, TSleeper> Service<TRequest>
+    for RetryService<TService, TService::Response, TService::Error, TSleeper>
+where
+    TService: Service<TRequest> + Clone + Send + 'static,
+    TService::Response: Send + 'static,
+    TService::Error: Send + 'static,
+    TService::Future: Send,
+    TRequest: Clone + Send + 'static,
+    TSleeper: AsyncSleeper + Clone + Send + Sync + 'static,
+    TSleeper::Sleep: Send
+{
+    type Response = TService::Response;
+    type Error = TService::Error;
+    type Future = Pin<Box<
+        dyn Future<Output = Result<TService::Response, TService::Error>> + Send
+    >>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), TService::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: TRequest) -> Self::Future {
+        // the first attempt uses the service `poll_ready`
+        // reserved, leaving a fresh clone in its place; later
+        // attempts each poll their own clone ready, since a
+        // reservation only covers a single call
+        let clone = self.inner.clone();
+        let mut ready = Some(std::mem::replace(&mut self.inner, clone));
+        let inner = self.inner.clone();
+        let backoff = self.backoff.clone();
+        let sleeper = self.sleeper.clone();
+
+        Box::pin(async move {
+            backoff.retry_async(&sleeper, move || {
+                let (mut service, reserved) = match ready.take() {
+                    Some(service) => (service, true),
+                    None => (inner.clone(), false)
+                };
+                let request = request.clone();
+
+                async move {
+                    if !reserved {
+                        poll_fn(|cx| service.poll_ready(cx)).await?;
+                    }
+                    service.call(request).await
+                }
+            }).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tower::RetryLayer;
+    use crate::{ExponentialBackoff, MockClock};
+    use std::future::{poll_fn, ready, Ready};
+    use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+    use std::sync::Arc;
+    use std::task::{Context, Poll};
+    use std::time::Duration;
+    use tower_layer::Layer;
+    use tower_service::Service;
+
+    /// Echoes its request back, after failing a set number of
+    /// calls.
+    #[derive(Clone)]
+    struct Flaky {
+        calls: Arc<AtomicU32>,
+        failures: u32
+    }
+
+    impl Service<String> for Flaky {
+        type Response = String;
+        type Error = String;
+        type Future = Ready<Result<String, String>>;
+
+        fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), String>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, request: String) -> Self::Future {
+            let call = self.calls.fetch_add(1, Ordering::SeqCst) + 1;
+            if call <= self.failures {
+                ready(Err(format!("call {} failed", call)))
+            } else {
+                ready(Ok(request))
+            }
+        }
+    }
+
+    /// Fails a set number of calls, and can only be ready for
+    /// one call at a time across all its clones, like a service
+    /// behind a concurrency limit of one.
+    struct Limited {
+        permit: Arc<AtomicBool>,
+        holds_permit: bool,
+        flaky: Flaky
+    }
+
+    impl Clone for Limited {
+        fn clone(&self) -> Limited {
+            Limited {
+                permit: self.permit.clone(),
+                holds_permit: false,
+                flaky: self.flaky.clone()
+            }
+        }
+    }
+
+    impl Service<String> for Limited {
+        type Response = String;
+        type Error = String;
+        type Future = Ready<Result<String, String>>;
+
+        fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), String>> {
+            if !self.holds_permit {
+                if !self.permit.swap(false, Ordering::SeqCst) {
+                    // a real limit would wake us once the permit
+                    // comes back
+                    return Poll::Pending;
+                }
+                self.holds_permit = true;
+            }
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, request: String) -> Self::Future {
+            assert!(self.holds_permit, "called without polling ready");
+            self.holds_permit = false;
+            let response = self.flaky.call(request);
+            self.permit.store(true, Ordering::SeqCst);
+            response
+        }
+    }
+
+    fn layer(clock: &MockClock) -> RetryLayer<String, String, MockClock> {
+        let backoff = ExponentialBackoff::new(
+            4, Duration::ZERO, Duration::from_millis(10), 1.0,
+            |result: &Result<String, String>| result.is_err()
+        );
+        RetryLayer::new(Arc::new(backoff), clock.clone())
+    }
+
+    #[tokio::test]
+    async fn retries_failed_calls() {
+        let clock = MockClock::new();
+        let calls = Arc::new(AtomicU32::new(0));
+        let mut service = layer(&clock).layer(
+            Flaky { calls: calls.clone(), failures: 2 }
+        );
+
+        poll_fn(|cx| service.poll_ready(cx)).await.unwrap();
+        let response = service.call("hello".to_string()).await;
+
+        assert_eq!(response, Ok("hello".to_string()));
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+        assert_eq!(clock.sleeps(), vec![
+            Duration::from_millis(10), Duration::from_millis(20)
+        ]);
+    }
+
+    #[tokio::test]
+    async fn gives_up_with_last_error() {
+        let clock = MockClock::new();
+        let calls = Arc::new(AtomicU32::new(0));
+        let mut service = layer(&clock).layer(
+            Flaky { calls: calls.clone(), failures: 10 }
+        );
+
+        poll_fn(|cx| service.poll_ready(cx)).await.unwrap();
+        let handle = tokio::spawn(service.call("hello".to_string()));
+
+        assert_eq!(handle.await.unwrap(), Err("call 4 failed".to_string()));
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn uses_the_readiness_it_reserved() {
+        let clock = MockClock::new();
+        let calls = Arc::new(AtomicU32::new(0));
+        let mut service = layer(&clock).layer(Limited {
+            permit: Arc::new(AtomicBool::new(true)),
+            holds_permit: false,
+            flaky: Flaky { calls: calls.clone(), failures: 2 }
+        });
+
+        poll_fn(|cx| service.poll_ready(cx)).await.unwrap();
+        let response = tokio::time::timeout(
+            Duration::from_secs(1), service.call("hello".to_string())
+        ).await;
+
+        assert_eq!(response, Ok(Ok("hello".to_string())));
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+}
@@ -0,0 +1,232 @@
+use std::any::type_name;
+use std::time::Duration;
+
+use tracing::field::Empty;
+use tracing::Span;
+
+use crate::ExponentialBackoff;
+
+/// Opens the span covering one call to a retry loop. The field
+/// names follow OpenTelemetry's semantic conventions where they
+/// have one. The attempt limit is left out for a backoff
+/// following its own schedule, which ignores `max_retries`.
+pub(crate) fn start<T, E>(backoff: &ExponentialBackoff<T, E>) -> Span {
+    tracing::info_span!(
+        "retry",
+        otel.kind = "internal",
+        otel.status_code = Empty,
+        retry.operation = backoff.operation.as_deref(),
+        retry.max_attempts = backoff.schedule.is_none().then_some(backoff.max_retries),
+        retry.attempts = Empty,
+        "error.type" = Empty,
+        exception.message = Empty
+    )
+}
+
+/// Records an attempt which is about to be retried after
+/// `delay`.
+pub(crate) fn retrying<T, E>(
+    span: &Span,
+    backoff: &ExponentialBackoff<T, E>,
+    attempt: u32,
+    result: &Result<T, E>,
+    delay: Duration
+) {
+    match result {
+        Ok(_) => tracing::info!(
+            parent: span,
+            retry.attempt = attempt,
+            retry.delay = delay.as_secs_f64(),
+            "attempt returned a value which will be retried"
+        ),
+        Err(error) => tracing::warn!(
+            parent: span,
+            retry.attempt = attempt,
+            retry.delay = delay.as_secs_f64(),
+            "error.type" = type_name::<E>(),
+            exception.message = message(backoff, error),
+            "attempt failed, retrying"
+        )
+    }
+}
+
+/// Records the end of the call with the final attempt's
+/// `result`.
+pub(crate) fn finished<T, E>(
+    span: &Span,
+    backoff: &ExponentialBackoff<T, E>,
+    attempts: u32,
+    result: &Result<T, E>
+) {
+    span.record("retry.attempts", attempts);
+
+    match result {
+        Ok(_) => {
+            span.record("otel.status_code", "OK");
+        },
+        Err(error) => {
+            let message = message(backoff, error);
+            span.record("otel.status_code", "ERROR");
+            span.record("error.type", type_name::<E>());
+            span.record("exception.message", message.as_deref());
+            tracing::error!(
+                parent: span,
+                retry.attempts = attempts,
+                "error.type" = type_name::<E>(),
+                exception.message = message,
+                "giving up"
+            );
+        }
+    }
+}
+
+fn message<T, E>(backoff: &ExponentialBackoff<T, E>, error: &E) -> Option<String> {
+    backoff.error_message.as_ref().map(|error_message| error_message(error))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ConstantBackoff, ExponentialBackoff, MockClock};
+    use std::io;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    /// Somewhere for the subscriber to write its log.
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Captured {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn traced<R>(run: impl FnOnce() -> R) -> (R, String) {
+        let captured = Captured::default();
+        let writer = captured.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(move || writer.clone())
+            .with_ansi(false)
+            .without_time()
+            .finish();
+
+        let result = tracing::subscriber::with_default(subscriber, run);
+        let log = String::from_utf8(captured.0.lock().unwrap().clone()).unwrap();
+        (result, log)
+    }
+
+    #[test]
+    fn traces_each_failed_attempt_and_giving_up() {
+        let backoff = ExponentialBackoff::new(
+            3, Duration::ZERO, Duration::from_millis(10), 1.0,
+            |r: &Result<(), &str>| r.is_err()
+        ).with_sleeper(MockClock::new()).with_error_messages();
+
+        let (result, log) = traced(|| backoff.retry(|| Err("refused")));
+        let lines: Vec<&str> = log.lines().collect();
+
+        assert_eq!(result, Err("refused"));
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].contains("WARN"));
+        assert!(lines[0].contains("retry{otel.kind=\"internal\" retry.max_attempts=3}"));
+        assert!(lines[0].contains("attempt failed, retrying"));
+        assert!(lines[0].contains("retry.attempt=1 retry.delay=0.01"));
+        assert!(lines[0].contains("error.type=\"&str\" exception.message=\"refused\""));
+        assert!(lines[1].contains("retry.attempt=2 retry.delay=0.02"));
+        assert!(lines[2].contains("ERROR"));
+        assert!(lines[2].contains("otel.status_code=\"ERROR\""));
+        assert!(lines[2].contains("giving up retry.attempts=3"));
+    }
+
+    #[test]
+    fn groups_what_attempts_trace_under_the_retry() {
+        let backoff = ExponentialBackoff::<(), &str>::builder()
+            .max_retries(2)
+            .sleeper(MockClock::new())
+            .error_messages()
+            .build()
+            .unwrap();
+
+        let (_, log) = traced(|| backoff.retry(|| {
+            tracing::info!("connecting");
+            Err("refused")
+        }));
+        let lines: Vec<&str> = log.lines().collect();
+
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].contains("retry{"));
+        assert!(lines[0].contains("connecting"));
+        assert!(lines[2].contains("retry{"));
+        assert!(lines[2].contains("connecting"));
+        assert!(lines[3].contains("exception.message=\"refused\""));
+    }
+
+    #[test]
+    fn groups_what_async_attempts_trace_under_the_retry() {
+        let backoff = ExponentialBackoff::new(
+            2, Duration::ZERO, Duration::from_millis(10), 1.0,
+            |r: &Result<(), &str>| r.is_err()
+        );
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+
+        let (_, log) = traced(|| runtime.block_on(
+            backoff.retry_async(&MockClock::new(), || async {
+                tracing::info!("connecting");
+                Err("refused")
+            })
+        ));
+        let lines: Vec<&str> = log.lines().collect();
+
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].contains("retry{"));
+        assert!(lines[0].contains("connecting"));
+        assert!(lines[2].contains("retry{"));
+        assert!(lines[2].contains("connecting"));
+    }
+
+    #[test]
+    fn leaves_out_attempt_limit_of_custom_schedules() {
+        let backoff = ExponentialBackoff::from_schedule(
+            ConstantBackoff::new(2, Duration::from_millis(10)),
+            |r: &Result<(), &str>| r.is_err()
+        ).with_sleeper(MockClock::new());
+
+        let (_, log) = traced(|| backoff.retry(|| Err("refused")));
+
+        assert!(log.contains("retry{otel.kind=\"internal\"}"));
+        assert!(!log.contains("retry.max_attempts"));
+    }
+
+    #[test]
+    fn leaves_out_error_messages_unless_asked() {
+        let backoff = ExponentialBackoff::new(
+            1, Duration::ZERO, Duration::from_millis(10), 1.0,
+            |r: &Result<(), &str>| r.is_err()
+        ).with_sleeper(MockClock::new());
+
+        let (_, log) = traced(|| backoff.retry(|| Err("secret")));
+
+        assert!(log.contains("giving up"));
+        assert!(!log.contains("secret"));
+    }
+
+    #[test]
+    fn stays_quiet_on_first_success() {
+        let backoff = ExponentialBackoff::new(
+            3, Duration::ZERO, Duration::from_millis(10), 1.0,
+            |r: &Result<(), &str>| r.is_err()
+        ).with_sleeper(MockClock::new());
+
+        let (result, log) = traced(|| backoff.retry(|| Ok(())));
+
+        assert_eq!(result, Ok(()));
+        assert!(log.is_empty());
+    }
+}